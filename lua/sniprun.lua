@@ -15,12 +15,11 @@ M.config_values = {
   selected_interpreters = {},
   repl_enable = {},
   repl_disable = {},
+  repl_per_buffer = false,
+  fallback_order = {},
 
-  interpreter_options = {
-    ["example_original"] = {
-      example_option = 2,
-    }
-  },
+  interpreter_options = {},
+  custom_interpreters = {},
 
   display = {
     "Classic",
@@ -31,7 +30,17 @@ M.config_values = {
     -- "Terminal"
     },
 
-  inline_messages = 0
+  inline_messages = 0,
+
+  timeout = 0,
+
+  stdin = "",
+
+  cwd = "file",
+  env = {},
+  env_file = "",
+
+  project_root_markers = {}
 }
 
 M.config_up=0
@@ -117,16 +126,23 @@ function M.configure_keymaps()
 
   vim.api.nvim_set_keymap("v", "<Plug>SnipRun", ":lua require'sniprun'.run('v')<CR>", {silent=true})
   vim.api.nvim_set_keymap("n", "<Plug>SnipRun", ":lua require'sniprun'.run()<CR>",{silent=true})
+  vim.api.nvim_set_keymap("n", "<Plug>SnipRunEnclosing", ":lua require'sniprun'.run('f')<CR>",{silent=true})
   vim.api.nvim_set_keymap("n", "<Plug>SnipRunOperator", ":set opfunc=SnipRunOperator<CR>g@",{silent=true})
+  vim.api.nvim_set_keymap("n", "<Plug>SnipStop", ":lua require'sniprun'.stop()<CR>",{silent=true})
   vim.api.nvim_set_keymap("n", "<Plug>SnipRTerminate", ":lua require'sniprun'.terminate()<CR>",{silent=true})
   vim.api.nvim_set_keymap("n", "<Plug>SnipReset", ":lua require'sniprun'.reset()<CR>",{silent=true})
   vim.api.nvim_set_keymap("n", "<Plug>SnipInfo", ":lua require'sniprun'.info()<CR>",{})
   vim.api.nvim_set_keymap("n", "<Plug>SnipReplMemoryClean", ":lua require'sniprun'.clear_repl()<CR>",{silent=true})
+  vim.api.nvim_set_keymap("n", "<Plug>SnipReplSessionDiscard", ":lua require'sniprun'.discard_sessions()<CR>",{silent=true})
   vim.api.nvim_set_keymap("n", "<Plug>SnipClose", ":lua require'sniprun.display'.close_all()<CR>",{silent=true})
 
+  vim.cmd("command! SnipRunEnclosing :lua require'sniprun'.run('f')")
+  vim.cmd("command! SnipStop :lua require'sniprun'.stop()")
   vim.cmd("command! SnipTerminate :lua require'sniprun'.terminate()")
   vim.cmd("command! SnipReset :lua require'sniprun'.reset()")
-  vim.cmd("command! SnipReplMemoryClean :lua require'sniprun'.clear_repl()")
+  vim.cmd("function! ListReplTargets(A,L,P) \n return ['all', 'buffer'] + ListInterpreters(a:A,a:L,a:P) \n endfunction")
+  vim.cmd("command! -nargs=? -complete=customlist,ListReplTargets SnipReplMemoryClean :lua require'sniprun'.clear_repl(<q-args>)")
+  vim.cmd("command! SnipReplSessionDiscard :lua require'sniprun'.discard_sessions()")
   vim.cmd("function! SnipRunOperator(...) \n lua require'sniprun'.run('n') \n endfunction")
   vim.cmd("command! SnipClose :lua require'sniprun.display'.close_all()")
 
@@ -157,12 +173,14 @@ end
 function M.run(mode)
   range_begin, range_end = M.get_range(mode)
   M.config_values["sniprun_root_dir"] = sniprun_path
-  M.notify('run', range_begin, range_end, M.config_values)
+  -- 'f' runs the function or block around the cursor, found with treesitter
+  local range_mode = (mode == 'f') and "enclosing" or ""
+  M.notify('run', range_begin, range_end, M.config_values, range_mode)
 end
 
 
 function M.get_range(mode)
-  if not mode then
+  if not mode or mode:match("[f]") then
     line1 = vim.api.nvim_win_get_cursor(0)[1]
     line2 = line1
   elseif mode:match("[w]") then
@@ -182,14 +200,25 @@ function M.get_range(mode)
 end
 
 
+function M.stop()
+  M.notify("stop")
+end
+
 function M.reset()
   M.notify("clean")
   vim.wait(200) -- let enough time for the rust binary to delete the cache before killing its process
   M.terminate()
 end
 
-function M.clear_repl()
-  M.notify("clearrepl")
+-- target: "all" (the default), "buffer" or an interpreter name
+function M.clear_repl(target)
+  M.notify("clearrepl", target or "", vim.api.nvim_get_current_buf())
+end
+
+-- the REPL sessions saved for the project of the current file
+function M.discard_sessions()
+  M.config_values["sniprun_root_dir"] = sniprun_path
+  M.notify("discardsessions", 1, 1, M.config_values)
 end
 
 function M.ping()
@@ -212,23 +241,17 @@ local function lines_from(file)
 end
 
 function M.info(arg)
-  if arg == nil or arg == "" then
-    M.config_values["sniprun_root_dir"] = sniprun_path
-    M.notify("info",1,1,M.config_values)
-
-    local sniprun_path = vim.fn.fnamemodify( vim.api.nvim_get_runtime_file("lua/sniprun.lua", false)[1], ":p:h") .. "/.."
-
-    if M.config_values.inline_messages ~= 0 then
-      vim.wait(500) -- let enough time for the sniprun binary to generate the file
-      print(" ")
-      local lines = lines_from(sniprun_path.."/ressources/infofile.txt")
-      -- print all lines content
-      for k,v in pairs(lines) do
-        print(v)
-      end
-    end
-  else --help about a particular interpreter
-      local lines = lines_from(sniprun_path.."/doc/"..string.gsub(arg,"%s+","")..".md")
+  M.config_values["sniprun_root_dir"] = sniprun_path
+  -- with an argument, the documentation and options of a particular interpreter
+  M.notify("info",1,1,M.config_values, string.gsub(arg or "","%s+",""))
+
+  local sniprun_path = vim.fn.fnamemodify( vim.api.nvim_get_runtime_file("lua/sniprun.lua", false)[1], ":p:h") .. "/.."
+
+  if M.config_values.inline_messages ~= 0 then
+    vim.wait(500) -- let enough time for the sniprun binary to generate the file
+    print(" ")
+    local lines = lines_from(sniprun_path.."/ressources/infofile.txt")
+    -- print all lines content
     for k,v in pairs(lines) do
       print(v)
     end
@@ -269,6 +292,33 @@ function M.health()
   if not M.file_exists(path_log_file)  then health_error("sniprun binary incompatible or crash at start", {"Compile sniprun locally, with a clean reinstall and 'bash ./install.sh 1' as post-install command."})
   else health_ok("sniprun binary runs correctly")
   end
+  health_start('Configuration')
+  path_health_file = os.getenv('HOME').."/.cache/sniprun/health.txt"
+  path_tools_file = os.getenv('HOME').."/.cache/sniprun/tools.txt"
+  os.remove(path_health_file)
+  os.remove(path_tools_file)
+  M.config_values["sniprun_root_dir"] = sniprun_path
+  M.notify("health",1,1,M.config_values)
+  vim.wait(1000, function() return M.file_exists(path_health_file) end)
+  if not M.file_exists(path_health_file) then health_warn("could not check the configuration")
+  else
+    local problems = lines_from(path_health_file)
+    if #problems == 0 then health_ok("configuration and interpreter options are valid") end
+    for _,problem in pairs(problems) do
+      health_warn(problem, {"see :SnipInfo <interpreter> for the valid options of an interpreter"})
+    end
+  end
+
+  health_start('Interpreters')
+  -- the version probes all run at the same time, and each is stopped after 5s
+  vim.wait(10000, function() return M.file_exists(path_tools_file) end)
+  if not M.file_exists(path_tools_file) then health_warn("could not check the executables needed by the interpreters")
+  else
+    for _,line in pairs(lines_from(path_tools_file)) do
+      if line:find("missing [^,]* %(required%)") then health_warn(line, {"install it to use this interpreter"})
+      else health_ok(line) end
+    end
+  end
 end
 
 function M.file_exists(name)
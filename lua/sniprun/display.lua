@@ -77,6 +77,43 @@ function M.write_to_term(message, ok)
 
 end
 
+function M.term_stream_start()
+  M.term_open()
+
+  local width = vim.api.nvim_win_get_width(M.term.window_handle)
+  local half_width = (width - 6) / 2
+  vim.api.nvim_chan_send(M.term.chan, string.rep("-",half_width).."-RUN--"..string.rep("-", half_width).."\n\r")
+  M.term.current_line = (M.term.current_line or -1) + 1
+end
+
+-- write a line of output of a snippet that is still running
+function M.term_stream_write(line)
+  M.term_open()
+  vim.api.nvim_chan_send(M.term.chan, line)
+  vim.api.nvim_chan_send(M.term.chan, "\n\r")
+  M.term.current_line = (M.term.current_line or -1) + 1
+end
+
+function M.term_stream_end(message, ok)
+  M.term_open()
+
+  local status = "ERROR-"
+  if ok then
+    status = "--OK--"
+  end
+  local width = vim.api.nvim_win_get_width(M.term.window_handle)
+  local half_width = (width - 6) / 2
+  message = string.rep("-",half_width)..status..string.rep("-", half_width).."\n"..message
+
+  local h = M.term.current_line or -1
+  for line in message:gmatch("([^\n]*)\n?") do
+    h = h + 1
+    vim.api.nvim_chan_send(M.term.chan, line)
+    vim.api.nvim_chan_send(M.term.chan, "\n\r")
+  end
+  M.term.current_line = h
+end
+
 
 function M.close_all()
   M.fw_close()
@@ -0,0 +1,282 @@
+//! The user configuration, sent by the lua side of sniprun along with every request
+
+use crate::custom::CustomInterpreter;
+use crate::display::DisplayType;
+use crate::environment::env_from_value;
+use crate::error::SniprunError;
+use neovim_lib::Value;
+use std::str::FromStr;
+
+///Typed user configuration; missing keys take the same defaults as the lua side
+#[derive(Clone, Debug, PartialEq)]
+pub struct SniprunConfig {
+    pub sniprun_root_dir: String,
+    pub selected_interpreters: Vec<String>,
+    pub repl_enable: Vec<String>,
+    pub repl_disable: Vec<String>,
+    pub repl_per_buffer: bool,
+    pub fallback_order: Vec<String>,
+    ///map of interpreter name -> map of its options
+    pub interpreter_options: Value,
+    pub display: Vec<DisplayType>,
+    pub inline_messages: bool,
+    pub timeout: u64,
+    pub stdin: String,
+    pub cwd: String,
+    pub env: Vec<(String, String)>,
+    pub env_file: String,
+    pub project_root_markers: Vec<String>,
+    pub custom_interpreters: Vec<CustomInterpreter>,
+}
+
+impl Default for SniprunConfig {
+    fn default() -> Self {
+        SniprunConfig {
+            sniprun_root_dir: String::new(),
+            selected_interpreters: vec![],
+            repl_enable: vec![],
+            repl_disable: vec![],
+            repl_per_buffer: false,
+            fallback_order: vec![],
+            interpreter_options: Value::Map(vec![]),
+            display: vec![DisplayType::Classic, DisplayType::VirtualTextOk],
+            inline_messages: false,
+            timeout: 0,
+            stdin: String::new(),
+            cwd: String::from("file"),
+            env: vec![],
+            env_file: String::new(),
+            project_root_markers: vec![],
+            custom_interpreters: vec![],
+        }
+    }
+}
+
+impl SniprunConfig {
+    ///Parse the config map. Keys of the wrong type are errors,
+    ///unknown keys and invalid custom interpreters are only reported in the returned warnings
+    pub fn from_value(value: &Value) -> Result<(Self, Vec<String>), SniprunError> {
+        let map = as_map("configuration", value)?;
+        let mut config = SniprunConfig::default();
+        let mut warnings = vec![];
+        for (key, value) in map {
+            let key = match key.as_str() {
+                Some(key) => key,
+                None => {
+                    warnings.push(format!("Ignored configuration key {}: not a string", key));
+                    continue;
+                }
+            };
+            match key {
+                "sniprun_root_dir" => config.sniprun_root_dir = as_string(key, value)?,
+                "selected_interpreters" => config.selected_interpreters = as_strings(key, value)?,
+                "repl_enable" => config.repl_enable = as_strings(key, value)?,
+                "repl_disable" => config.repl_disable = as_strings(key, value)?,
+                "repl_per_buffer" => config.repl_per_buffer = as_bool(key, value)?,
+                "fallback_order" => config.fallback_order = as_strings(key, value)?,
+                "interpreter_options" => {
+                    let mut options = vec![];
+                    for (name, interpreter_options) in as_map(key, value)? {
+                        let name_str = name.as_str().unwrap_or_default();
+                        let key = format!("{}.{}", key, name_str);
+                        let interpreter_options = as_map(&key, interpreter_options)?;
+                        options.push((name.clone(), Value::Map(interpreter_options.to_vec())));
+                    }
+                    config.interpreter_options = Value::Map(options);
+                }
+                "display" => {
+                    config.display = as_strings(key, value)?
+                        .iter()
+                        .map(|d| DisplayType::from_str(d))
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| invalid(key, &e.to_string()))?
+                }
+                "inline_messages" => config.inline_messages = as_bool(key, value)?,
+                "timeout" => config.timeout = as_number(key, value)?,
+                "stdin" => config.stdin = as_string(key, value)?,
+                "cwd" => config.cwd = as_string(key, value)?,
+                "env" => config.env = env_from_value(&Value::Map(as_map(key, value)?.to_vec())),
+                "env_file" => config.env_file = as_string(key, value)?,
+                "project_root_markers" => config.project_root_markers = as_strings(key, value)?,
+                "custom_interpreters" => {
+                    //a broken definition only loses that interpreter, not the whole config
+                    for (name, definition) in as_map(key, value)? {
+                        match as_string(key, name)
+                            .and_then(|name| CustomInterpreter::from_value(&name, definition))
+                        {
+                            Ok(interpreter) => config.custom_interpreters.push(interpreter),
+                            Err(e) => warnings.push(format!("{}, interpreter ignored", e)),
+                        }
+                    }
+                }
+                _ => warnings.push(format!("Unknown configuration key '{}', ignored", key)),
+            }
+        }
+        Ok((config, warnings))
+    }
+}
+
+///options the user set for the given interpreter
+pub fn interpreter_options<'a>(options: &'a Option<Value>, interpreter: &str) -> Option<&'a Value> {
+    get(options.as_ref()?, interpreter)
+}
+
+///value of an option of the given interpreter, eg: interpreter_options = { Interpreter_name = { key = value } }
+pub fn interpreter_option(options: &Option<Value>, interpreter: &str, key: &str) -> Option<Value> {
+    get(interpreter_options(options, interpreter)?, key).cloned()
+}
+
+fn get<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|kv| kv.0.as_str() == Some(key))
+        .map(|kv| &kv.1)
+}
+
+fn invalid(key: &str, message: &str) -> SniprunError {
+    SniprunError::CustomError(format!("Invalid configuration for '{}': {}", key, message))
+}
+
+fn mismatch(key: &str, expected: &str, value: &Value) -> SniprunError {
+    invalid(key, &format!("expected {}, got {}", expected, value))
+}
+
+fn as_map<'a>(key: &str, value: &'a Value) -> Result<&'a [(Value, Value)], SniprunError> {
+    match value {
+        Value::Map(map) => Ok(map),
+        // lua can't tell an empty table from an empty list
+        Value::Array(a) if a.is_empty() => Ok(&[]),
+        _ => Err(mismatch(key, "a table", value)),
+    }
+}
+
+fn as_string(key: &str, value: &Value) -> Result<String, SniprunError> {
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| mismatch(key, "a string", value))
+}
+
+fn as_number(key: &str, value: &Value) -> Result<u64, SniprunError> {
+    value
+        .as_u64()
+        .ok_or_else(|| mismatch(key, "a positive number", value))
+}
+
+///a boolean, or 0/1 like vimscript ones
+fn as_bool(key: &str, value: &Value) -> Result<bool, SniprunError> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        _ => Ok(as_number(key, value)? == 1),
+    }
+}
+
+fn as_strings(key: &str, value: &Value) -> Result<Vec<String>, SniprunError> {
+    match value {
+        Value::Array(a) => a.iter().map(|v| as_string(key, v)).collect(),
+        Value::Map(m) if m.is_empty() => Ok(vec![]),
+        _ => Err(mismatch(key, "a list of strings", value)),
+    }
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Value::from(k), v))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn defaults_and_warnings() {
+        let value = map(vec![
+            (
+                "repl_enable",
+                Value::Array(vec![Value::from("Python3_original")]),
+            ),
+            ("timeout", Value::from(10)),
+            ("repl_per_buffer", Value::from(true)),
+            ("env", Value::Array(vec![])),
+            ("typo", Value::from(1)),
+        ]);
+        let (config, warnings) = SniprunConfig::from_value(&value).unwrap();
+        assert_eq!(config.repl_enable, vec![String::from("Python3_original")]);
+        assert_eq!(config.repl_disable, Vec::<String>::new());
+        assert_eq!(config.timeout, 10);
+        assert!(config.repl_per_buffer);
+        assert!(!config.inline_messages);
+        assert_eq!(config.cwd, "file");
+        assert_eq!(
+            warnings,
+            vec![String::from("Unknown configuration key 'typo', ignored")]
+        );
+    }
+
+    #[test]
+    fn readable_errors() {
+        let value = map(vec![("timeout", Value::from("ten"))]);
+        assert_eq!(
+            SniprunConfig::from_value(&value),
+            Err(SniprunError::CustomError(String::from(
+                "Invalid configuration for 'timeout': expected a positive number, got \"ten\""
+            )))
+        );
+        let value = map(vec![("display", Value::Array(vec![Value::from("Clasic")]))]);
+        assert!(SniprunConfig::from_value(&value).is_err());
+    }
+
+    #[test]
+    fn invalid_custom_interpreter() {
+        let value = map(vec![(
+            "custom_interpreters",
+            map(vec![
+                (
+                    "Good",
+                    map(vec![
+                        ("filetypes", Value::from("txt")),
+                        ("run", Value::from("cat {file}")),
+                    ]),
+                ),
+                (
+                    "Bad",
+                    map(vec![
+                        ("filetypes", Value::from("txt")),
+                        ("runn", Value::from("cat {file}")),
+                    ]),
+                ),
+            ]),
+        )]);
+        let (config, warnings) = SniprunConfig::from_value(&value).unwrap();
+        assert_eq!(config.custom_interpreters.len(), 1);
+        assert_eq!(config.custom_interpreters[0].name, "Good");
+        assert_eq!(
+            warnings,
+            vec![String::from(
+                "Invalid configuration for 'custom_interpreters.Bad': unknown key \"runn\", interpreter ignored"
+            )]
+        );
+    }
+
+    #[test]
+    fn get_interpreter_option() {
+        let value = map(vec![(
+            "interpreter_options",
+            map(vec![(
+                "GFM_original",
+                map(vec![("default_filetype", Value::from("bash"))]),
+            )]),
+        )]);
+        let (config, _) = SniprunConfig::from_value(&value).unwrap();
+        let options = Some(config.interpreter_options);
+        assert_eq!(
+            interpreter_option(&options, "GFM_original", "default_filetype"),
+            Some(Value::from("bash"))
+        );
+        assert_eq!(interpreter_option(&options, "GFM_original", "other"), None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Interpreters defined in the user configuration, that run shell command templates:
+//! custom_interpreters = { Name = { filetypes = {...}, extension = "...", run = "...", ... } }
+
+use crate::error::SniprunError;
+use crate::interpreter::{process_settings, SupportLevel};
+use crate::job::{add_artifact, run_command, set_interpreter, set_phase, Phase};
+use crate::requirement::Requirement;
+use crate::DataHolder;
+use log::info;
+use neovim_lib::Value;
+use std::process::Command;
+
+///An interpreter from the user config. In the templates, {file} is the source file, {bin}
+///the same path without extension (for the output of a compiler) and {dir} their directory,
+///already quoted for the shell
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomInterpreter {
+    pub name: String,
+    pub filetypes: Vec<String>,
+    pub extension: String,
+    ///command that compiles the source file, if the language needs it
+    pub build: Option<String>,
+    pub run: String,
+    ///the code is put in place of {code}
+    pub boilerplate: String,
+}
+
+impl CustomInterpreter {
+    pub fn from_value(name: &str, value: &Value) -> Result<Self, SniprunError> {
+        let invalid = |message: &str| {
+            SniprunError::CustomError(format!(
+                "Invalid configuration for 'custom_interpreters.{}': {}",
+                name, message
+            ))
+        };
+        let map = value.as_map().ok_or_else(|| invalid("expected a table"))?;
+        let get = |key: &str| {
+            map.iter()
+                .find(|kv| kv.0.as_str() == Some(key))
+                .map(|kv| &kv.1)
+        };
+        let string = |key: &str| -> Result<Option<String>, SniprunError> {
+            match get(key) {
+                None => Ok(None),
+                Some(v) => v
+                    .as_str()
+                    .map(|s| Some(s.to_string()))
+                    .ok_or_else(|| invalid(&format!("'{}' should be a string, got {}", key, v))),
+            }
+        };
+        for (key, _) in map {
+            let known = ["filetypes", "extension", "build", "run", "boilerplate"];
+            if !known.contains(&key.as_str().unwrap_or_default()) {
+                return Err(invalid(&format!("unknown key {}", key)));
+            }
+        }
+
+        let filetypes = match get("filetypes") {
+            Some(Value::Array(a)) => a
+                .iter()
+                .map(|v| v.as_str().map(String::from))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("'filetypes' should be a list of strings"))?,
+            Some(Value::String(s)) => vec![s.as_str().unwrap_or_default().to_string()],
+            _ => return Err(invalid("'filetypes' is missing")),
+        };
+        let boilerplate = string("boilerplate")?.unwrap_or_else(|| String::from("{code}"));
+        if !boilerplate.contains("{code}") {
+            return Err(invalid("'boilerplate' should contain {code}"));
+        }
+        Ok(CustomInterpreter {
+            name: name.to_string(),
+            filetypes,
+            extension: string("extension")?
+                .map(|e| e.trim_start_matches('.').to_string())
+                .unwrap_or_else(|| String::from("txt")),
+            build: string("build")?.filter(|b| !b.trim().is_empty()),
+            run: string("run")?.ok_or_else(|| invalid("'run' is missing"))?,
+            boilerplate,
+        })
+    }
+
+    pub fn supports(&self, filetype: &str) -> bool {
+        self.filetypes.iter().any(|f| f == filetype)
+    }
+
+    ///the programs the build and run commands start, when they start with one found in the PATH
+    ///(after the variable assignments), not with a shell builtin or a path
+    pub fn requirements(&self) -> Vec<Requirement> {
+        const BUILTINS: &[&str] = &[
+            "cd", "exec", "eval", "export", "set", "source", "test", "true", "false", "echo",
+            "printf", "command", "if", "for", "while", "case",
+        ];
+        let mut programs: Vec<&str> = vec![];
+        for command in self.build.iter().chain(std::iter::once(&self.run)) {
+            let program = command.split_whitespace().find(|word| !word.contains('='));
+            match program {
+                Some(program)
+                    if program
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_-.+".contains(c))
+                        && !program.starts_with('.')
+                        && !BUILTINS.contains(&program)
+                        && !programs.contains(&program) =>
+                {
+                    programs.push(program)
+                }
+                _ => (),
+            }
+        }
+        programs
+            .into_iter()
+            .map(|program| Requirement::required(program, &["--version"]))
+            .collect()
+    }
+
+    ///one line per setting, for ':SnipInfo <name>'
+    pub fn describe(&self) -> Vec<String> {
+        let mut v = vec![
+            format!("{} (custom interpreter)", self.name),
+            format!("  filetypes: {}", self.filetypes.join(", ")),
+            format!("  extension: {}", self.extension),
+        ];
+        if let Some(build) = &self.build {
+            v.push(format!("  build: {}", build));
+        }
+        v.push(format!("  run: {}", self.run));
+        v.push(format!(
+            "  boilerplate: {}",
+            self.boilerplate.replace('\n', "\\n")
+        ));
+        v
+    }
+
+    ///write the code in the boilerplate to a file, build it if needed, and run it
+    pub fn run(&self, data: &DataHolder) -> Result<String, SniprunError> {
+        set_interpreter(data, &self.name, SupportLevel::Bloc);
+        let code = if !data.current_bloc.trim().is_empty() {
+            &data.current_bloc
+        } else {
+            &data.current_line
+        };
+        let dir = format!("{}/custom/{}", data.work_dir, self.name);
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            SniprunError::InternalError(format!("Could not create directory {}: {}", dir, e))
+        })?;
+        let bin = format!("{}/main", dir);
+        let file = format!("{}.{}", bin, self.extension);
+        std::fs::write(&file, self.boilerplate.replace("{code}", code))
+            .map_err(|e| SniprunError::InternalError(format!("Could not write {}: {}", file, e)))?;
+        let quote = |path: &str| format!("'{}'", path.replace('\'', "'\\''"));
+        let fill = |template: &str| {
+            template
+                .replace("{file}", &quote(&file))
+                .replace("{bin}", &quote(&bin))
+                .replace("{dir}", &quote(&dir))
+        };
+        let settings = process_settings(data, &self.name, true);
+
+        if let Some(build) = &self.build {
+            set_phase(data, Phase::Build);
+            info!("[CUSTOM] building with: {}", fill(build));
+            let output = run_command(
+                data,
+                Command::new("sh").arg("-c").arg(fill(build)),
+                None,
+                &settings,
+            )?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let first_line = stderr.lines().find(|l| !l.trim().is_empty());
+                return Err(SniprunError::CompilationError(
+                    first_line.unwrap_or_default().to_string(),
+                ));
+            }
+            if std::path::Path::new(&bin).exists() {
+                add_artifact(data, &bin);
+            }
+        }
+
+        set_phase(data, Phase::Execute);
+        info!("[CUSTOM] running with: {}", fill(&self.run));
+        let output = run_command(
+            data,
+            Command::new("sh").arg("-c").arg(fill(&self.run)),
+            None,
+            &settings,
+        )?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(SniprunError::RuntimeError(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_custom {
+    use super::*;
+
+    fn definition(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Value::from(k), v))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn build_and_run() {
+        let value = definition(vec![
+            ("filetypes", Value::Array(vec![Value::from("shout")])),
+            ("extension", Value::from(".txt")),
+            ("build", Value::from("tr a-z A-Z < {file} > {bin}")),
+            ("run", Value::from("cat {bin}")),
+            ("boilerplate", Value::from("<{code}>")),
+        ]);
+        let interpreter = CustomInterpreter::from_value("Shout", &value).unwrap();
+        assert!(interpreter.supports("shout"));
+        assert_eq!(interpreter.requirements().len(), 2);
+
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("hello");
+        assert_eq!(interpreter.run(&data).unwrap(), "<HELLO>");
+
+        // in a directory with spaces and quotes
+        let work_dir =
+            std::env::temp_dir().join(format!("sniprun custom's {}", std::process::id()));
+        data.work_dir = work_dir.to_string_lossy().to_string();
+        assert_eq!(interpreter.run(&data).unwrap(), "<HELLO>");
+        std::fs::remove_dir_all(&work_dir).unwrap();
+
+        let programs = |build: &str, run: &str| {
+            let value = definition(vec![
+                ("filetypes", Value::from("shout")),
+                ("build", Value::from(build)),
+                ("run", Value::from(run)),
+            ]);
+            let interpreter = CustomInterpreter::from_value("Shout", &value).unwrap();
+            interpreter
+                .requirements()
+                .iter()
+                .map(|r| r.executable.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            programs("FOO=1 gcc {file}", "cd {dir} && ./{bin}"),
+            vec!["gcc"]
+        );
+        assert_eq!(programs("./tool {file}", "perl {file}"), vec!["perl"]);
+
+        let missing_run = definition(vec![("filetypes", Value::from("shout"))]);
+        assert!(CustomInterpreter::from_value("Shout", &missing_run).is_err());
+    }
+}
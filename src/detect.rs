@@ -0,0 +1,149 @@
+//! Filetype of the code when neovim doesn't know it (scratch buffers, extensionless scripts):
+//! from a `#!` shebang, or failing that from what the code looks like
+
+use regex::Regex;
+
+///filetype of the scripts run by each program
+const PROGRAMS: [(&str, &str); 19] = [
+    ("python", "python"),
+    ("pypy", "python"),
+    ("node", "javascript"),
+    ("nodejs", "javascript"),
+    ("deno", "javascript"),
+    ("bash", "sh"),
+    ("sh", "sh"),
+    ("dash", "sh"),
+    ("zsh", "zsh"),
+    ("ruby", "ruby"),
+    ("lua", "lua"),
+    ("luajit", "lua"),
+    ("Rscript", "r"),
+    ("julia", "julia"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("runghc", "haskell"),
+    ("runhaskell", "haskell"),
+    ("scala", "scala"),
+];
+
+///patterns typical of each language: the filetype with the most matching lines wins
+const HEURISTICS: [(&str, &str); 6] = [
+    (
+        "python",
+        r"^(def \w+\(.*\)\s*(->.*)?:|class \w+.*:|from [\w.]+ import |import \w+$|if __name__ ==)",
+    ),
+    (
+        "rust",
+        r"fn \w+\(|\blet mut \w|\w+!\(|^use \w+::|^impl\b|->\s*\w+\s*\{",
+    ),
+    (
+        "javascript",
+        r"console\.\w+\(|^(const|var) \w+ =|=>\s*\{|\brequire\(|^function \w+\(.*\)\s*\{",
+    ),
+    (
+        "lua",
+        r"^\s*local \w+|\bthen$|\bvim\.\w|^\s*end$|\bfunction\s*[\w.:]*\(.*\)$",
+    ),
+    (
+        "sh",
+        r"^\s*(echo|export|fi$|done$|esac$)\b|\$\(|\$\{\w+|^\s*for \w+ in .*;\s*do|^\s*if \[",
+    ),
+    ("c", r"^#include\s*<"),
+];
+
+///the filetype of a script starting with that line, if it is a known shebang
+pub fn shebang_filetype(first_line: &str) -> Option<String> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // '#!/usr/bin/env -S python3 -u': the first word that is not a flag
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    PROGRAMS
+        .iter()
+        .find(|(p, _)| *p == program)
+        .map(|(_, filetype)| filetype.to_string())
+}
+
+///the filetype the code most likely is, if it looks like one
+pub fn guess_filetype(code: &str) -> Option<String> {
+    let mut best: Option<(&str, usize)> = None;
+    for (filetype, pattern) in HEURISTICS.iter() {
+        let re = Regex::new(pattern).unwrap();
+        let score = code.lines().filter(|line| re.is_match(line)).count();
+        if score > best.map(|b| b.1).unwrap_or(0) {
+            best = Some((filetype, score));
+        }
+    }
+    best.map(|(filetype, _)| filetype.to_string())
+}
+
+///filetype to use when neovim has none: the shebang of the file or of the code, then a guess
+pub fn filetype(file_first_line: &str, code: &str) -> Option<String> {
+    shebang_filetype(file_first_line)
+        .or_else(|| shebang_filetype(code.lines().next().unwrap_or_default()))
+        .or_else(|| guess_filetype(code))
+}
+
+///whether Lua code uses the neovim API, so must be run by neovim
+pub fn uses_vim_api(code: &str) -> bool {
+    Regex::new(r"\bvim\.\w").unwrap().is_match(code)
+}
+
+#[cfg(test)]
+mod test_detect {
+    use super::*;
+
+    #[test]
+    fn shebangs() {
+        let shebang = |line| shebang_filetype(line);
+        assert_eq!(
+            shebang("#!/usr/bin/python3.11"),
+            Some(String::from("python"))
+        );
+        assert_eq!(
+            shebang("#!/usr/bin/env node"),
+            Some(String::from("javascript"))
+        );
+        assert_eq!(
+            shebang("#!/usr/bin/env -S bash -e"),
+            Some(String::from("sh"))
+        );
+        assert_eq!(shebang("#! /bin/sh"), Some(String::from("sh")));
+        assert_eq!(shebang("#!/usr/bin/unknown"), None);
+        assert_eq!(shebang("print(1)"), None);
+    }
+
+    #[test]
+    fn heuristics() {
+        let guess = |code| guess_filetype(code);
+        assert_eq!(
+            guess("import os\ndef f(x):\n    return x"),
+            Some(String::from("python"))
+        );
+        assert_eq!(
+            guess("let mut a = 1;\nprintln!(\"{}\", a);"),
+            Some(String::from("rust"))
+        );
+        assert_eq!(
+            guess("const a = 1;\nconsole.log(a);"),
+            Some(String::from("javascript"))
+        );
+        assert_eq!(
+            guess("local a = 1\nif a then\n  print(a)\nend"),
+            Some(String::from("lua"))
+        );
+        assert_eq!(
+            guess("for f in *; do\n  echo \"$f\"\ndone"),
+            Some(String::from("sh"))
+        );
+        assert_eq!(guess("hello world"), None);
+        assert_eq!(
+            filetype("#!/usr/bin/env ruby", "puts 1"),
+            Some(String::from("ruby"))
+        );
+        assert!(uses_vim_api("vim.api.nvim_echo({{'hi'}}, false, {})"));
+        assert!(!uses_vim_api("print(\"vim is great\")"));
+    }
+}
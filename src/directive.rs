@@ -0,0 +1,39 @@
+//! Inline directives: settings given from inside the snippet, usually in a comment, eg:
+//!
+//! ```text
+//! # sniprun: stdin=file:input.txt
+//! ```
+//!
+//! A directive line holds a single `key=value` pair, the value is the rest of the line.
+
+/// closing comment markers that may end a directive line
+const COMMENT_ENDS: [&str; 3] = ["*/", "-->", "-}"];
+
+/// value of the first directive with the given key found in the code, if any
+pub fn find(code: &str, key: &str) -> Option<String> {
+    code.lines().find_map(|line| {
+        let (_, directive) = line.split_once("sniprun:")?;
+        let (k, value) = directive.split_once('=')?;
+        if k.trim() != key {
+            return None;
+        }
+        let mut value = value.trim();
+        for end in COMMENT_ENDS.iter() {
+            value = value.trim_end_matches(end).trim_end();
+        }
+        Some(value.to_string())
+    })
+}
+
+#[cfg(test)]
+mod test_directive {
+    use super::*;
+
+    #[test]
+    fn find_directive() {
+        let code = "int a;\n/* sniprun: stdin=text:1 2 */\n# sniprun: other=3";
+        assert_eq!(find(code, "stdin"), Some(String::from("text:1 2")));
+        assert_eq!(find(code, "other"), Some(String::from("3")));
+        assert_eq!(find(code, "missing"), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::SniprunError;
+use crate::job::RunResult;
 use crate::{DataHolder, ReturnMessageType};
 use log::info;
 use neovim_lib::{Neovim, NeovimApi};
@@ -48,20 +49,33 @@ impl fmt::Display for DisplayType {
     }
 }
 
-pub fn display(result: Result<String, SniprunError>, nvim: Arc<Mutex<Neovim>>, data: &DataHolder) {
+pub fn display(run: RunResult, nvim: Arc<Mutex<Neovim>>, data: &DataHolder) {
     let mut display_type = data.display_type.clone();
     display_type.sort();
     display_type.dedup(); //now only uniques display types
     info!("Display type chosen: {:?}", display_type);
+    info!(
+        "Ran with {} at level {}, exit status {:?}, build {:?}, run {:?}, artifacts {:?}",
+        run.interpreter,
+        run.support_level,
+        run.exit_status,
+        run.build_time,
+        run.run_time,
+        run.artifacts
+    );
 
+    // short display types only show the interpreter's result, bigger ones also have room
+    // for what it left out (warnings of a successful run, stdout printed before a crash)
+    let result = &run.result;
+    let detailed = run.detailed();
     for dt in display_type.iter() {
         match dt {
-            Classic => return_message_classic(&result, &nvim, &data.return_message_type),
-            VirtualTextOk => display_virtual_text(&result, &nvim, &data, true),
-            VirtualTextErr => display_virtual_text(&result, &nvim, &data, false),
-            Terminal => display_terminal(&result, &nvim),
-            LongTempFloatingWindow => display_floating_window(&result, &nvim, &data, true),
-            TempFloatingWindow => display_floating_window(&result, &nvim, &data, false),
+            Classic => return_message_classic(result, &nvim, &data.return_message_type),
+            VirtualTextOk => display_virtual_text(result, &nvim, &data, true),
+            VirtualTextErr => display_virtual_text(result, &nvim, &data, false),
+            Terminal => display_terminal(&detailed, &nvim, data),
+            LongTempFloatingWindow => display_floating_window(&detailed, &nvim, &data, true),
+            TempFloatingWindow => display_floating_window(&detailed, &nvim, &data, false),
         }
     }
 }
@@ -108,7 +122,31 @@ pub fn display_virtual_text(
     info!("done displaying virtual text, {:?}", res);
 }
 
-pub fn display_terminal(message: &Result<String, SniprunError>, nvim:&Arc<Mutex<Neovim>>) {
+pub fn display_terminal(
+    message: &Result<String, SniprunError>,
+    nvim: &Arc<Mutex<Neovim>>,
+    data: &DataHolder,
+) {
+    let streamed = match &data.job {
+        Some(job) => job.lock().unwrap().has_streamed(),
+        None => false,
+    };
+    if streamed {
+        // output is already in the terminal, only the final status is left to write
+        let res = match message {
+            Ok(_) => nvim
+                .lock()
+                .unwrap()
+                .command("lua require\"sniprun.display\".term_stream_end(\"\", true)"),
+            Err(result) => nvim.lock().unwrap().command(&format!(
+                "lua require\"sniprun.display\".term_stream_end(\"{}\", false)",
+                cleanup_and_escape(&result.to_string()),
+            )),
+        };
+        info!("res = {:?}", res);
+        return;
+    }
+
     let res = match message {
         Ok(result) => nvim.lock().unwrap().command(&format!(
             "lua require\"sniprun.display\".write_to_term(\"{}\", true)",
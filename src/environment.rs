@@ -0,0 +1,146 @@
+//! Where and with which environment variables the processes of a run are started
+
+use crate::DataHolder;
+use log::info;
+use neovim_lib::Value;
+use std::path::{Path, PathBuf};
+
+///Working directory and extra environment of the processes spawned by an interpreter
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Environment {
+    ///None to keep sniprun's own working directory
+    pub cwd: Option<PathBuf>,
+    pub vars: Vec<(String, String)>,
+}
+
+impl Environment {
+    ///Resolve the user settings (global ones, overridden by the interpreter's if any):
+    /// - cwd: "file" (the directory of the current file), "project" (the project root), or a path
+    /// - env_file: a .env file to load, relative to the working directory, ignored if missing
+    /// - env: extra variables, that take precedence over the ones of the .env file
+    pub fn new(
+        data: &DataHolder,
+        cwd: Option<&str>,
+        env_file: Option<&str>,
+        env: Option<&Value>,
+    ) -> Self {
+        let cwd = working_dir(data, cwd.unwrap_or(&data.cwd));
+
+        let mut vars = vec![];
+        let env_file = env_file.unwrap_or(&data.env_file);
+        if !env_file.is_empty() {
+            let path = match &cwd {
+                Some(dir) if Path::new(env_file).is_relative() => dir.join(env_file),
+                _ => PathBuf::from(expand_path(env_file, data)),
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(content) => vars.extend(parse_env_file(&content)),
+                Err(e) => info!("[ENV] could not read {}: {}", path.display(), e),
+            }
+        }
+        vars.extend(data.env.iter().cloned());
+        if let Some(env) = env {
+            vars.extend(env_from_value(env));
+        }
+        Environment { cwd, vars }
+    }
+}
+
+fn working_dir(data: &DataHolder, policy: &str) -> Option<PathBuf> {
+    let file_dir = Path::new(&data.filepath)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf);
+    match policy {
+        "" | "file" => file_dir,
+        "project" if !data.projectroot.is_empty() => Some(PathBuf::from(&data.projectroot)),
+        "project" => file_dir,
+        path => Some(PathBuf::from(expand_path(path, data))),
+    }
+}
+
+///expand '~', and make relative paths start from the directory of the current file
+pub fn expand_path(path: &str, data: &DataHolder) -> String {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest).to_string_lossy().to_string();
+        }
+    }
+    match Path::new(&data.filepath).parent() {
+        Some(dir) if Path::new(path).is_relative() => dir.join(path).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+///variables of a { NAME = "value" } map from the user config
+pub fn env_from_value(env: &Value) -> Vec<(String, String)> {
+    env.as_map()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(k, v)| {
+                    let value = match v.as_str() {
+                        Some(s) => s.to_string(),
+                        None => v.to_string(),
+                    };
+                    Some((k.as_str()?.to_string(), value))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+///parse the usual NAME=value lines of a .env file, ignoring comments and malformed lines
+pub fn parse_env_file(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some(q @ ('"' | '\'')) if value.len() > 1 && value.ends_with(q) => {
+                    &value[1..value.len() - 1]
+                }
+                _ => value,
+            };
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_environment {
+    use super::*;
+
+    #[test]
+    fn env_file() {
+        let content = "# comment\nA=1\nexport B = \"two words\"\nmalformed\nC='3'";
+        assert_eq!(
+            parse_env_file(content),
+            vec![
+                (String::from("A"), String::from("1")),
+                (String::from("B"), String::from("two words")),
+                (String::from("C"), String::from("3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn working_dir_policy() {
+        let mut data = DataHolder::new();
+        data.filepath = String::from("/tmp/project/src/main.py");
+        let env = Environment::new(&data, None, None, None);
+        assert_eq!(env.cwd, Some(PathBuf::from("/tmp/project/src")));
+
+        let env = Environment::new(&data, Some("project"), None, None);
+        assert_eq!(env.cwd, Some(PathBuf::from("/tmp/project/src")));
+        data.projectroot = String::from("/tmp/project");
+        let env = Environment::new(&data, Some("project"), None, None);
+        assert_eq!(env.cwd, Some(PathBuf::from("/tmp/project")));
+
+        let env = Environment::new(&data, Some("data"), None, None);
+        assert_eq!(env.cwd, Some(PathBuf::from("/tmp/project/src/data")));
+    }
+}
@@ -1,6 +1,7 @@
+use crate::job::Phase;
 use thiserror::Error;
 
-#[derive(Debug, Error, Eq, PartialEq)]
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
 #[allow(dead_code)]
 pub enum SniprunError {
     ///this error should only be raised when something goes very wrong, and you can't figure out
@@ -31,6 +32,12 @@ pub enum SniprunError {
     /// errors raised if the user code is incorrect and fail a run-time (and not because the language interpreter failed to fetch the needed code/imports
     #[error("RuntimeError: {0}")]
     RuntimeError(String),
+    /// raised when a process spawned during the given phase ran past the configured timeout (in seconds)
+    #[error("Timeout: the {0} phase took more than {1}s")]
+    TimeoutError(Phase, u64),
+    /// raised when the user stopped the run before its completion
+    #[error("Cancelled")]
+    Cancelled,
     ///custom error for advanced interpreters, the error will be displayed as-is
     #[error("{0}")]
     CustomError(String),
@@ -1,7 +1,15 @@
 use crate::error::SniprunError;
+use crate::display::DisplayType::Terminal;
+use crate::environment::Environment;
+use crate::job::{set_interpreter, set_phase, Phase, ProcessSettings};
+use crate::repl::SessionKey;
+use crate::requirement::Requirement;
 use crate::DataHolder;
 use log::info;
+use neovim_lib::Value;
 use std::fmt::Display;
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 #[allow(dead_code)]
@@ -39,6 +47,87 @@ impl Display for SupportLevel {
     }
 }
 
+///Type of the value of an interpreter option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum OptionKind {
+    String,
+    Bool,
+    Integer,
+    ///list of strings
+    List,
+    Table,
+}
+
+impl OptionKind {
+    ///whether the value given by the user has this type
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            OptionKind::String => value.is_str(),
+            OptionKind::Bool => value.is_bool() || matches!(value.as_u64(), Some(0) | Some(1)),
+            OptionKind::Integer => value.is_i64() || value.is_u64(),
+            OptionKind::List => value
+                .as_array()
+                .is_some_and(|a| a.iter().all(Value::is_str)),
+            // lua can't tell an empty table from an empty list
+            OptionKind::Table => value.is_map() || value.as_array().is_some_and(|a| a.is_empty()),
+        }
+    }
+}
+
+impl Display for OptionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        match *self {
+            OptionKind::String => f.write_str("string"),
+            OptionKind::Bool => f.write_str("boolean"),
+            OptionKind::Integer => f.write_str("integer"),
+            OptionKind::List => f.write_str("list of strings"),
+            OptionKind::Table => f.write_str("table"),
+        }
+    }
+}
+
+///An option an interpreter accepts in the user config:
+///interpreter_options = { Interpreter_name = { name = value } }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpreterOption {
+    pub name: String,
+    pub kind: OptionKind,
+    pub description: String,
+}
+
+impl InterpreterOption {
+    pub fn new(name: &str, kind: OptionKind, description: &str) -> Self {
+        InterpreterOption {
+            name: name.to_string(),
+            kind,
+            description: description.to_string(),
+        }
+    }
+}
+
+///Options every interpreter accepts, since they are handled by sniprun itself
+pub fn common_options() -> Vec<InterpreterOption> {
+    vec![
+        InterpreterOption::new(
+            "timeout",
+            OptionKind::Integer,
+            "kill processes that run for longer than this (in seconds, 0 = never)",
+        ),
+        InterpreterOption::new(
+            "cwd",
+            OptionKind::String,
+            "working directory: \"file\", \"project\" or a path",
+        ),
+        InterpreterOption::new("env", OptionKind::Table, "extra environment variables"),
+        InterpreterOption::new(
+            "env_file",
+            OptionKind::String,
+            ".env file to load, relative to the working directory",
+        ),
+    ]
+}
+
 ///This is the trait all interpreters must implement.
 ///The launcher run fucntions new() and run() from this trait.
 pub trait Interpreter: ReplLikeInterpreter {
@@ -58,12 +147,25 @@ pub trait Interpreter: ReplLikeInterpreter {
         false
     }
 
+    ///Whether the code looks written for this interpreter rather than for another one of the
+    ///same filetype (eg: it uses an API only this one provides), None if it can't tell.
+    ///The launcher tries the ones that match first, and the ones that don't last
+    fn matches_code(_code: &str) -> Option<bool> {
+        None
+    }
+
     /// The languages (as filetype codes) supported by your interpreter; check ':set ft?' in neovim
     /// on a file of your language if you are not sure. You can put whatever (python and python3),
     /// but I strongly recommend making the first element the name of the langage ("JavaScript"
     /// instead of "js" for example)
     fn get_supported_languages() -> Vec<String>;
 
+    ///Filetypes the user set this interpreter up for (through its interpreter_options), on top
+    ///of the supported languages
+    fn get_configured_languages(_data: &DataHolder) -> Vec<String> {
+        vec![]
+    }
+
     fn get_current_level(&self) -> SupportLevel;
     fn set_current_level(&mut self, level: SupportLevel);
     fn get_data(&self) -> DataHolder;
@@ -86,6 +188,18 @@ pub trait Interpreter: ReplLikeInterpreter {
         None
     }
 
+    /// The options this interpreter accepts in interpreter_options (besides the common ones),
+    /// they are listed by ':SnipInfo <name>', and the health check flags the other ones
+    fn get_options() -> Vec<InterpreterOption> {
+        vec![]
+    }
+
+    /// The executables this interpreter runs, the launcher skips the interpreter when a required
+    /// one is missing; they are listed by ':SnipInfo <name>' and the health check
+    fn get_requirements() -> Vec<Requirement> {
+        vec![]
+    }
+
     ///Disable REPL-like behavior by default
     fn behave_repl_like_default() -> bool {
         false
@@ -96,6 +210,12 @@ pub trait Interpreter: ReplLikeInterpreter {
         false
     }
 
+    /// Whether the output of the executed code can be shown as it arrives (with the Terminal
+    /// display), ie the result of execute() is the raw stdout of the processes it runs
+    fn has_streaming_capability() -> bool {
+        false
+    }
+
     ///If the interpreter has treesitter capabilities
     fn has_treesitter_capability() -> bool {
         false
@@ -123,10 +243,18 @@ pub trait Interpreter: ReplLikeInterpreter {
         if let Some(res) = self.fallback() {
             return res;
         }
+        let data = self.get_data();
+        set_interpreter(&data, &Self::get_name(), level);
         self.fetch_code()
             .and_then(|_| self.add_boilerplate())
-            .and_then(|_| self.build())
-            .and_then(|_| self.execute())
+            .and_then(|_| {
+                set_phase(&data, Phase::Build);
+                self.build()
+            })
+            .and_then(|_| {
+                set_phase(&data, Phase::Execute);
+                self.execute()
+            })
     }
 
     fn run_at_level_repl(&mut self, level: SupportLevel) -> Result<String, SniprunError> {
@@ -135,10 +263,18 @@ pub trait Interpreter: ReplLikeInterpreter {
         if let Some(res) = self.fallback() {
             return res;
         }
+        let data = self.get_data();
+        set_interpreter(&data, &Self::get_name(), level);
         self.fetch_code_repl()
             .and_then(|_| self.add_boilerplate_repl())
-            .and_then(|_| self.build_repl())
-            .and_then(|_| self.execute_repl())
+            .and_then(|_| {
+                set_phase(&data, Phase::Build);
+                self.build_repl()
+            })
+            .and_then(|_| {
+                set_phase(&data, Phase::Execute);
+                self.execute_repl()
+            })
     }
 
     /// default run function ran from the launcher (run_at_level(max_level))
@@ -157,65 +293,53 @@ pub trait Interpreter: ReplLikeInterpreter {
 }
 
 pub trait InterpreterUtils {
-    ///read previously saved code from the interpreterdata object
-    fn read_previous_code(&self) -> String;
     ///append code to the interpreterdata object
     fn save_code(&self, code: String);
     fn clear(&self);
 
     fn set_pid(&self, pid: u32);
     fn get_pid(&self) -> Option<u32>;
+
+    fn add_artifact(&self, path: &str);
+
+    fn get_interpreter_option(&self, key: &str) -> Option<Value>;
+    fn get_string_option(&self, key: &str, default: &str) -> String;
+    fn get_bool_option(&self, key: &str, default: bool) -> bool;
+    fn get_int_option(&self, key: &str, default: i64) -> i64;
+    fn get_list_option(&self, key: &str, default: Vec<String>) -> Vec<String>;
+
+    fn run_command(&self, command: &mut Command) -> Result<Output, SniprunError>;
+    fn run_command_with_stdin(
+        &self,
+        command: &mut Command,
+        stdin: Stdio,
+    ) -> Result<Output, SniprunError>;
+    ///timeout, streaming, working directory and environment of the processes spawned by this interpreter
+    fn process_settings(&self) -> ProcessSettings;
 }
 
 impl<T: Interpreter> InterpreterUtils for T {
-    ///Read a String previous saved to sniprun memory
-    fn read_previous_code(&self) -> String {
-        let data = self.get_data();
-        if data.interpreter_data.is_none() {
-            return String::new();
-        } else {
-            let interpreter_data = data.interpreter_data.unwrap().lock().unwrap().clone();
-            let content_owner = T::get_name();
-            if interpreter_data.owner == content_owner {
-                return interpreter_data.content;
-            } else {
-                return String::new();
-            }
-        }
-    }
-
     /// Save an unique String to Sniprun memory.
     /// This will be emptied at neovim startup,
     /// when sniprun is reset or memoryclean'd
     fn save_code(&self, code: String) {
-        let previous_code = self.read_previous_code();
         let data = self.get_data();
-        if data.interpreter_data.is_none() {
-            info!("Unable to save code for next usage");
-            return;
+        if let Some(di) = &data.interpreter_data {
+            let mut di = di.lock().unwrap();
+            let session = di.session(&SessionKey::new(&data, &T::get_name()));
+            session.content = session.content.clone() + "\n" + &code;
         } else {
-            {
-                data.interpreter_data.clone().unwrap().lock().unwrap().owner = T::get_name();
-            }
-            data.interpreter_data.unwrap().lock().unwrap().content = previous_code + "\n" + &code;
+            info!("Unable to save code for next usage");
         }
     }
 
     /// Clear sniprun memory
     fn clear(&self) {
         let data = self.get_data();
-        if data.interpreter_data.is_some() {
-            data.interpreter_data
-                .clone()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .owner
-                .clear();
-            data.interpreter_data
-                .unwrap()
-                .lock()
+        if let Some(di) = &data.interpreter_data {
+            di.lock()
                 .unwrap()
+                .session(&SessionKey::new(&data, &T::get_name()))
                 .content
                 .clear();
         }
@@ -226,23 +350,160 @@ impl<T: Interpreter> InterpreterUtils for T {
     /// This will be emptied at neovim startup,
     /// when sniprun is reset or memoryclean'd
     fn set_pid(&self, pid: u32) {
-        if let Some(di) = self.get_data().interpreter_data {
-            di.lock().unwrap().pid = Some(pid);
+        let data = self.get_data();
+        if let Some(di) = &data.interpreter_data {
+            di.lock()
+                .unwrap()
+                .session(&SessionKey::new(&data, &T::get_name()))
+                .pid = Some(pid);
         }
     }
 
     /// get a pid previously saved in sniprun memory
     fn get_pid(&self) -> Option<u32> {
-        if let Some(di) = self.get_data().interpreter_data {
-            if let Some(real_pid) = di.lock().unwrap().pid {
-                return Some(real_pid);
-            } else {
-                return None;
+        let data = self.get_data();
+        data.interpreter_data.as_ref().and_then(|di| {
+            di.lock()
+                .unwrap()
+                .session(&SessionKey::new(&data, &T::get_name()))
+                .pid
+        })
+    }
+
+    /// record a file produced by the run (binary, plot...), so it can be reported to the user
+    fn add_artifact(&self, path: &str) {
+        crate::job::add_artifact(&self.get_data(), path);
+    }
+
+    /// get the value of an option set by the user for this interpreter, eg:
+    /// interpreter_options = { Interpreter_name = { key = value } }
+    fn get_interpreter_option(&self, key: &str) -> Option<Value> {
+        crate::config::interpreter_option(&self.get_data().interpreter_options, &T::get_name(), key)
+    }
+
+    /// typed getters for interpreter options: the default is returned if the user did not set
+    /// the option, or if the value has the wrong type
+    fn get_string_option(&self, key: &str, default: &str) -> String {
+        match self.get_interpreter_option(key) {
+            Some(Value::String(s)) if s.as_str().is_some() => s.into_str().unwrap(),
+            Some(v) => {
+                info!("option {} of {} is not a string: {}", key, T::get_name(), v);
+                default.to_string()
             }
-        } else {
-            return None;
+            None => default.to_string(),
+        }
+    }
+
+    fn get_bool_option(&self, key: &str, default: bool) -> bool {
+        match self.get_interpreter_option(key) {
+            Some(Value::Boolean(b)) => b,
+            // vimscript-style booleans
+            Some(v) if OptionKind::Bool.matches(&v) => v.as_u64() == Some(1),
+            Some(v) => {
+                info!(
+                    "option {} of {} is not a boolean: {}",
+                    key,
+                    T::get_name(),
+                    v
+                );
+                default
+            }
+            None => default,
         }
     }
+
+    fn get_int_option(&self, key: &str, default: i64) -> i64 {
+        match self.get_interpreter_option(key).map(|v| (v.as_i64(), v)) {
+            Some((Some(i), _)) => i,
+            Some((None, v)) => {
+                info!(
+                    "option {} of {} is not an integer: {}",
+                    key,
+                    T::get_name(),
+                    v
+                );
+                default
+            }
+            None => default,
+        }
+    }
+
+    fn get_list_option(&self, key: &str, default: Vec<String>) -> Vec<String> {
+        match self.get_interpreter_option(key) {
+            Some(v) if OptionKind::List.matches(&v) => v
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|s| s.as_str().unwrap().to_string())
+                .collect(),
+            Some(v) => {
+                info!(
+                    "option {} of {} is not a list of strings: {}",
+                    key,
+                    T::get_name(),
+                    v
+                );
+                default
+            }
+            None => default,
+        }
+    }
+
+    /// run an external command (compiler, interpreter, binary...) and wait for its output.
+    /// Use this instead of `Command::output()` so the user can stop the run, so
+    /// the configured timeout is enforced, and so the user code gets the configured stdin
+    fn run_command(&self, command: &mut Command) -> Result<Output, SniprunError> {
+        crate::job::run_command(&self.get_data(), command, None, &self.process_settings())
+    }
+
+    /// same as run_command, but feeds the given stdin to the process
+    fn run_command_with_stdin(
+        &self,
+        command: &mut Command,
+        stdin: Stdio,
+    ) -> Result<Output, SniprunError> {
+        crate::job::run_command(
+            &self.get_data(),
+            command,
+            Some(stdin),
+            &self.process_settings(),
+        )
+    }
+
+    fn process_settings(&self) -> ProcessSettings {
+        process_settings(
+            &self.get_data(),
+            &T::get_name(),
+            T::has_streaming_capability(),
+        )
+    }
+}
+
+///the settings of InterpreterUtils::process_settings, from the options of the interpreter of that
+///name: also for the interpreters that don't implement the Interpreter trait (see custom.rs)
+pub fn process_settings(data: &DataHolder, name: &str, streaming: bool) -> ProcessSettings {
+    let option = |key| crate::config::interpreter_option(&data.interpreter_options, name, key);
+    let string_option = |key, default: &str| {
+        option(key)
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_else(|| default.to_string())
+    };
+    // 0 means no timeout, a per-interpreter timeout overrides the global one
+    let timeout = option("timeout")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(data.timeout as i64)
+        .max(0) as u64;
+    let env = Environment::new(
+        data,
+        Some(&string_option("cwd", &data.cwd)),
+        Some(&string_option("env_file", &data.env_file)),
+        option("env").as_ref(),
+    );
+    ProcessSettings {
+        timeout: Some(Duration::from_secs(timeout)).filter(|t| !t.is_zero()),
+        stream: streaming && data.display_type.contains(&Terminal),
+        env,
+    }
 }
 
 pub trait ReplLikeInterpreter {
@@ -261,3 +522,38 @@ pub trait ReplLikeInterpreter {
         )))
     }
 }
+
+#[cfg(test)]
+mod test_interpreter {
+    use super::*;
+    use crate::interpreters::JS_original;
+
+    #[test]
+    fn list_option() {
+        let mut data = DataHolder::new();
+        data.interpreter_options = Some(Value::from(vec![(
+            Value::from("JS_original"),
+            Value::from(vec![
+                (
+                    Value::from("flags"),
+                    Value::from(vec![Value::from("--a"), Value::from("--b")]),
+                ),
+                (Value::from("not_a_list"), Value::from(3)),
+            ]),
+        )]));
+        let interpreter = JS_original::new(data);
+        assert_eq!(
+            interpreter.get_list_option("flags", vec![]),
+            vec![String::from("--a"), String::from("--b")]
+        );
+        let default = vec![String::from("x")];
+        assert_eq!(
+            interpreter.get_list_option("not_a_list", default.clone()),
+            default
+        );
+        assert_eq!(
+            interpreter.get_list_option("missing", default.clone()),
+            default
+        );
+    }
+}
@@ -51,6 +51,14 @@ impl Interpreter for Ada_original {
         String::from("Ada_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("gnatmake", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_current_level(&self) -> SupportLevel {
         self.support_level
     }
@@ -104,25 +112,26 @@ impl Interpreter for Ada_original {
         write(&self.main_file_path, &self.code)
             .expect("Unable to write to file for language_subname");
 
-        let output = Command::new("gnatmake")
-            .arg("main")
-            .arg(&self.main_file_path)
-            .current_dir(&self.ada_work_dir)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("gnatmake")
+                .arg("-D")
+                .arg(&self.ada_work_dir)
+                .arg("-o")
+                .arg(&self.bin_path)
+                .arg(&self.main_file_path),
+        )?;
         if !output.status.success() {
             return Err(SniprunError::CompilationError(
                 String::from_utf8(output.stderr).unwrap(),
             ));
         }
 
+        self.add_artifact(&self.bin_path);
         return Ok(());
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
 
         if output.status.success() {
             //return stdout
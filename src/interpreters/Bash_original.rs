@@ -30,6 +30,18 @@ impl Interpreter for Bash_original {
         String::from("Bash_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("bash", &["--version"])]
+    }
+
+    fn has_treesitter_capability() -> bool {
+        true
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn behave_repl_like_default() -> bool {
         true
     }
@@ -99,10 +111,7 @@ impl Interpreter for Bash_original {
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("bash")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("bash").arg(&self.main_file_path))?;
         info!("yay from bash interpreter");
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
@@ -116,63 +125,102 @@ impl Interpreter for Bash_original {
 
 impl ReplLikeInterpreter for Bash_original {
     fn fetch_code_repl(&mut self) -> Result<(), SniprunError> {
-        self.fetch_code()?;
-        let previous_code = self.read_previous_code();
-        self.code = previous_code + "\n" + &self.code;
-        Ok(())
+        self.fetch_code()
     }
 
     fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
-        self.add_boilerplate()
+        Ok(())
     }
 
     fn build_repl(&mut self) -> Result<(), SniprunError> {
-        self.build()
+        Ok(())
     }
 
     fn execute_repl(&mut self) -> Result<String, SniprunError> {
-        fn strip_prints(code: &str) -> String {
-            let mut striped_code = String::new();
-            let print_statements = vec!["echo ", "print "];
-            let mut count = 0;
-            for line in code.lines() {
-                //basic splitting only
-                let opening_bracket = line.matches("{").count();
-                let closing_bracket = line.matches("}").count();
-                count += opening_bracket - closing_bracket;
-                if count <= 0 {
-                    if print_statements.iter().all(|ps| !line.contains(ps)) {
-                        // does not contains any print statement
-                        striped_code.push_str(line);
-                        striped_code.push_str("\n");
-                    }
-                } else {
-                    striped_code.push_str(line);
-                    striped_code.push_str("\n");
-                }
-            }
-            return striped_code;
-        }
+        // the code runs in a bash process that stays alive between runs, so that variables,
+        // functions and the working directory persist without running the earlier code again
+        let settings = self.process_settings();
+        let process = crate::repl::process(
+            &self.data,
+            &Bash_original::get_name(),
+            "bash",
+            || {
+                let mut bash = Command::new("bash");
+                bash.arg("--noprofile").arg("--norc");
+                bash
+            },
+            &settings,
+        )?;
+        // the pid is saved to the session before the process is locked for the run: the
+        // interpreter data must not be locked while the process lock is held
+        let pid = process.lock().unwrap().id();
+        self.set_pid(pid);
+        let restored = crate::repl::restored_code(&self.data, &Bash_original::get_name());
 
-        let res = self.execute();
-        if res.is_ok() {
-            let _ = self.fetch_code();
-            self.save_code(strip_prints(&self.code));
+        // the code gets its own stdin, the process' one is where the next commands come from
+        let stdin = if self.data.stdin.is_empty() {
+            String::from("/dev/null")
+        } else {
+            let input = self
+                .data
+                .stdin
+                .parse::<crate::stdin::StdinSource>()?
+                .read(&self.data)?;
+            let path = self.bash_work_dir.clone() + "/stdin";
+            write(&path, input).expect("Unable to write stdin file for bash-original");
+            path
+        };
+        let output = {
+            let mut process = process.lock().unwrap();
+            if let Some(code) = restored {
+                info!("running again the code of the restored session");
+                let _ = self.send(&mut process, &code, "/dev/null");
+            }
+            self.send(&mut process, &self.code, &stdin)?
+        };
+        info!("executed as repl");
+        if output.status.success() {
+            // to run it again when the session is restored
+            self.save_code(self.code.clone());
+            Ok(String::from_utf8(output.stdout).unwrap())
+        } else {
+            Err(SniprunError::RuntimeError(
+                String::from_utf8(output.stderr).unwrap(),
+            ))
         }
+    }
+}
 
-        info!("executed as repl");
-        return res;
+impl Bash_original {
+    ///run the code in the REPL process, reading the stdin file
+    fn send(
+        &self,
+        process: &mut crate::repl::ReplProcess,
+        code: &str,
+        stdin: &str,
+    ) -> Result<std::process::Output, SniprunError> {
+        let marker = process.new_marker();
+        let input = format!(
+            "eval '{}' < '{}'\n__sniprun_status=$?\necho \"{m} $__sniprun_status\"\necho \"{m} $__sniprun_status\" >&2\n",
+            code.replace('\'', "'\\''"),
+            stdin,
+            m = marker
+        );
+        process.send(&self.data, &input, &marker, &self.process_settings())
     }
 }
 
 #[cfg(test)]
 mod test_bash_original {
     use super::*;
+    use crate::InterpreterData;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn run_all() {
         simple_print();
         block_things();
+        repl_state();
     }
     fn simple_print() {
         let mut data = DataHolder::new();
@@ -195,4 +243,28 @@ mod test_bash_original {
         let string_result = res.unwrap();
         assert_eq!(string_result, "2\n");
     }
+
+    fn repl_state() {
+        let interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        let mut data = DataHolder::new();
+        data.interpreter_data = interpreter_data.clone();
+        data.current_bloc = String::from("A=3; echo \"it's $A\" > /dev/null; mkdir sniprun_$$");
+        data.cwd = std::env::temp_dir().display().to_string();
+        let mut interpreter = Bash_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "");
+        let pid = interpreter.get_pid().unwrap();
+
+        // mkdir is not run again, and the variable and working directory are kept
+        data.current_bloc = String::from("cd sniprun_$$ && rmdir ../sniprun_$$ && echo $A");
+        let mut interpreter = Bash_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "3\n");
+        assert_eq!(interpreter.get_pid(), Some(pid));
+
+        data.current_bloc = String::from("echo oops >&2; false");
+        let mut interpreter = Bash_original::new(data);
+        assert_eq!(
+            interpreter.run(),
+            Err(SniprunError::RuntimeError(String::from("oops\n")))
+        );
+    }
 }
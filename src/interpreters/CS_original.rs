@@ -30,6 +30,14 @@ impl Interpreter for CS_original {
         String::from("CS_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("coffee", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_supported_languages() -> Vec<String> {
         vec![
             String::from("CoffeeScript"),
@@ -89,10 +97,7 @@ impl Interpreter for CS_original {
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("coffee")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("coffee").arg(&self.main_file_path))?;
         info!("yay from cs interpreter");
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
@@ -62,6 +62,14 @@ impl Interpreter for C_original {
         String::from("C_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("gcc", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -117,12 +125,12 @@ impl Interpreter for C_original {
         let mut _file =
             File::create(&self.main_file_path).expect("Failed to create file for c-original");
         write(&self.main_file_path, &self.code).expect("Unable to write to file for c-original");
-        let output = Command::new(&self.compiler)
-            .arg(&self.main_file_path)
-            .arg("-o")
-            .arg(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new(&self.compiler)
+                .arg(&self.main_file_path)
+                .arg("-o")
+                .arg(&self.bin_path),
+        )?;
 
         //TODO if relevant, return the error number (parse it from stderr)
         if !output.status.success() {
@@ -151,14 +159,13 @@ impl Interpreter for C_original {
 
             return Err(SniprunError::CompilationError(relevant_error));
         } else {
+            self.add_artifact(&self.bin_path);
             return Ok(());
         }
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
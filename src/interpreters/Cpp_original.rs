@@ -67,6 +67,14 @@ impl Interpreter for Cpp_original {
         String::from("Cpp_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("g++", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_current_level(&self) -> SupportLevel {
         self.support_level
     }
@@ -120,25 +128,24 @@ impl Interpreter for Cpp_original {
         let mut _file =
             File::create(&self.main_file_path).expect("Failed to create file for rust-original");
         write(&self.main_file_path, &self.code).expect("Unable to write to file for rust-original");
-        let output = Command::new(&self.compiler)
-            .arg(&self.main_file_path)
-            .arg("-o")
-            .arg(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new(&self.compiler)
+                .arg(&self.main_file_path)
+                .arg("-o")
+                .arg(&self.bin_path),
+        )?;
 
         //TODO if relevant, return the error number (parse it from stderr)
         if !output.status.success() {
             return Err(SniprunError::CompilationError("".to_string()));
         } else {
+            self.add_artifact(&self.bin_path);
             return Ok(());
         }
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
@@ -39,6 +39,14 @@ impl Interpreter for D_original {
         String::from("D_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("dmd", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_current_level(&self) -> SupportLevel {
         self.support_level
     }
@@ -93,11 +101,7 @@ impl Interpreter for D_original {
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new("dmd")
-            .arg("-run")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("dmd").arg("-run").arg(&self.main_file_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
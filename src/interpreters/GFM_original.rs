@@ -104,18 +104,6 @@ impl ReplLikeInterpreter for GFM_original {}
 
 impl Interpreter for GFM_original {
     fn new_with_level(data: DataHolder, support_level: SupportLevel) -> Box<Self> {
-        fn index_from_name(
-            name: &str,
-            config: &Vec<(neovim_lib::Value, neovim_lib::Value)>,
-        ) -> Option<usize> {
-            for (i, kv) in config.iter().enumerate() {
-                if name == kv.0.as_str().unwrap() {
-                    return Some(i);
-                }
-            }
-            info!("key not found in GFM");
-            return None;
-        }
         //create a subfolder in the cache folder
         let lwd = data.work_dir.clone() + "/gfm_original";
         let mut builder = DirBuilder::new();
@@ -126,36 +114,23 @@ impl Interpreter for GFM_original {
         let mut data_clone = data.clone();
         data_clone.work_dir = lwd.clone(); //trick other interpreter at creating their files here
 
-        let mut ddf = String::from("python"); //default default
-
-        // this is the ugliness required to fetch something from the interpreter options, to adapt
-        // ofc to the type you want to convert to
-        if let Some(config) = data.interpreter_options {
-            if let Some(ar) = config.as_map() {
-                if let Some(i) = index_from_name("interpreter_options", ar) {
-                    if let Some(ar2) = ar[i].1.as_map() {
-                        if let Some(i) = index_from_name("GFM_original", ar2) {
-                            if let Some(gfm_config) = ar2[i].1.as_map() {
-                                if let Some(i) = index_from_name("default_filetype", gfm_config) {
-                                    if let Some(other_default) = gfm_config[i].1.as_str() {
-                                        info!("Changed default filetype");
-                                        ddf = other_default.to_string();
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        Box::new(GFM_original {
+        let mut gfm = Box::new(GFM_original {
             data: data_clone,
             support_level,
             code: String::new(),
             language_work_dir: lwd,
-            default_filetype: ddf,
-        })
+            default_filetype: String::new(),
+        });
+        gfm.default_filetype = gfm.get_string_option("default_filetype", "python"); //default default
+        gfm
+    }
+
+    fn get_options() -> Vec<InterpreterOption> {
+        vec![InterpreterOption::new(
+            "default_filetype",
+            OptionKind::String,
+            "language of the code blocs that do not specify one",
+        )]
     }
 
     fn get_supported_languages() -> Vec<String> {
@@ -265,5 +240,22 @@ mod test_GFM_original {
         let string_result = res.unwrap();
         assert_eq!(string_result, "3\n");
     }
+
+    #[test]
+    fn default_filetype_option() {
+        let options = |value: neovim_lib::Value| {
+            Some(neovim_lib::Value::from(vec![(
+                neovim_lib::Value::from("GFM_original"),
+                neovim_lib::Value::from(vec![(neovim_lib::Value::from("default_filetype"), value)]),
+            )]))
+        };
+        let mut data = DataHolder::new();
+        data.interpreter_options = options(neovim_lib::Value::from("bash"));
+        assert_eq!(GFM_original::new(data.clone()).default_filetype, "bash");
+
+        // wrong type: back to the default
+        data.interpreter_options = options(neovim_lib::Value::from(3));
+        assert_eq!(GFM_original::new(data).default_filetype, "python");
+    }
 }
         
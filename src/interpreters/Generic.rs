@@ -75,7 +75,19 @@ impl Interpreter for Generic {
             + &self.data.filetype
             + "\",\"files\":[{\"name\": \"name.any\",\"content\":\""
             + &self.code.replace("\\\"", "\"").replace("\"", "\\\"")
-            + "\"}]}";
+            + "\"}]";
+        //glot feeds the code its own stdin, since it reads the json from ours
+        if !self.data.stdin.is_empty() {
+            let input = self
+                .data
+                .stdin
+                .parse::<crate::stdin::StdinSource>()?
+                .read(&self.data)?;
+            self.code = self.code.clone()
+                + ",\"stdin\":"
+                + &serde_json::to_string(&String::from_utf8_lossy(&input)).unwrap();
+        }
+        self.code.push('}');
         Ok(())
     }
 
@@ -92,13 +104,10 @@ impl Interpreter for Generic {
             "executing generic: args are glotpath:{}, jsonpath:{}",
             &self.glot_bin_path, &self.main_file_path
         );
-        let output = Command::new(&self.glot_bin_path)
-            .stdin(File::open(&self.main_file_path).unwrap())
-            .output()
-            .expect(&format!(
-                "Unable to start process, bin path : {}",
-                &self.glot_bin_path
-            ));
+        let output = self.run_command_with_stdin(
+            &mut Command::new(&self.glot_bin_path),
+            File::open(&self.main_file_path).unwrap().into(),
+        )?;
         info!(
             "generic executed, status.success?:{}",
             output.status.success()
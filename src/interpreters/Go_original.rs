@@ -46,6 +46,14 @@ impl Interpreter for Go_original {
         String::from("Go_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("go", &["version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -97,27 +105,26 @@ impl Interpreter for Go_original {
         write(&self.main_file_path, &self.code).expect("Unable to write to file for go-original");
 
         //compile it (to the bin_path that arleady points to the rigth path)
-        let output = Command::new("go")
-            .arg("build")
-            .arg("-o")
-            .arg(&self.go_work_dir)
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("go")
+                .arg("build")
+                .arg("-o")
+                .arg(&self.go_work_dir)
+                .arg(&self.main_file_path),
+        )?;
 
         //TODO if relevant, return the error number (parse it from stderr)
         if !output.status.success() {
             return Err(SniprunError::CompilationError("".to_string()));
         } else {
+            self.add_artifact(&self.bin_path);
             return Ok(());
         }
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
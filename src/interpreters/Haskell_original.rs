@@ -46,6 +46,14 @@ impl Interpreter for Haskell_original {
         String::from("Haskell_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("ghc", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -102,13 +110,13 @@ impl Interpreter for Haskell_original {
             "haskell interpreter : main & bin paths are {}, {}",
             &self.main_file_path, &self.bin_path
         );
-        let output = Command::new("ghc")
-            .arg("-dynamic")
-            .arg("-o")
-            .arg(self.bin_path.clone())
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("ghc")
+                .arg("-dynamic")
+                .arg("-o")
+                .arg(self.bin_path.clone())
+                .arg(&self.main_file_path),
+        )?;
 
         info!("code : {:?}", &self.code);
         //TODO if relevant, return the error number (parse it from stderr)
@@ -117,15 +125,14 @@ impl Interpreter for Haskell_original {
                 String::from_utf8(output.stderr).unwrap(),
             ));
         } else {
+            self.add_artifact(&self.bin_path);
             return Ok(());
         }
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
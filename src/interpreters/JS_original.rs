@@ -8,6 +8,120 @@ pub struct JS_original {
     main_file_path: String,
 }
 impl ReplLikeInterpreter for JS_original {}
+impl JS_original {
+    /// the index of the closing '/' of the regex literal at `i`, None if it is a division
+    fn regex_end(chars: &[char], i: usize) -> Option<usize> {
+        let ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+        let before: String = chars[..i].iter().collect();
+        let before = before.trim_end();
+        // a division follows a value, a regex follows an operator or a keyword
+        if before.ends_with(|c: char| ident(c) || c == ')' || c == ']') {
+            let word = &before[before.trim_end_matches(ident).len()..];
+            let keywords =
+                "return typeof instanceof in of new delete void throw case do else yield await";
+            if !keywords.split(' ').any(|k| k == word) {
+                return None;
+            }
+        }
+        let mut in_class = false;
+        let mut j = i + 1;
+        while j < chars.len() {
+            match chars[j] {
+                '\\' => j += 1,
+                '[' => in_class = true,
+                ']' => in_class = false,
+                '/' if !in_class => return Some(j),
+                // regex literals are on one line
+                '\n' => return None,
+                _ => (),
+            }
+            j += 1;
+        }
+        None
+    }
+
+    /// like the node REPL, print the value of the expression the code ends with
+    fn print_last_expression(code: &str) -> String {
+        // the last statement follows the last top-level ';', block or line break
+        let chars: Vec<char> = code.trim_end().chars().collect();
+        let mut statement_start = 0;
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match quote {
+                Some(_) if c == '\\' => i += 1,
+                Some(q) if c == q => quote = None,
+                Some(_) => (),
+                None => match c {
+                    '\'' | '"' | '`' => quote = Some(c),
+                    '/' if chars.get(i + 1) == Some(&'/') => {
+                        while i < chars.len() && chars[i] != '\n' {
+                            i += 1;
+                        }
+                        continue;
+                    }
+                    '/' if chars.get(i + 1) == Some(&'*') => {
+                        i += 3;
+                        while i < chars.len() && !(chars[i - 1] == '*' && chars[i] == '/') {
+                            i += 1;
+                        }
+                    }
+                    '/' => {
+                        if let Some(end) = JS_original::regex_end(&chars, i) {
+                            i = end;
+                        }
+                    }
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' => depth -= 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            statement_start = i + 1;
+                        }
+                    }
+                    ';' | '\n' if depth == 0 => {
+                        // unless the next line continues the expression
+                        let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                        if c == ';' || !matches!(next, Some('.') | Some('?') | Some(':')) {
+                            statement_start = i + 1;
+                        }
+                    }
+                    _ => (),
+                },
+            }
+            i += 1;
+        }
+        let statement: String = chars[statement_start.min(chars.len())..].iter().collect();
+        let statement = statement.trim();
+        let first_word = statement
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or("");
+        let statements = [
+            "async", "break", "class", "const", "continue", "do", "export", "for", "function",
+            "if", "import", "let", "return", "switch", "throw", "try", "var", "while",
+        ];
+        let assignment =
+            regex::Regex::new(r"^[\w.\[\]'\x22]+\s*([-+*/%&|^]|\*\*|<<|>>>?|&&|\|\||\?\?)?=[^=>]")
+                .unwrap();
+        if statement.is_empty()
+            || depth != 0
+            || quote.is_some()
+            || statements.contains(&first_word)
+            || assignment.is_match(statement)
+        {
+            return code.to_string();
+        }
+        let before: String = chars[..statement_start].iter().collect();
+        before
+            + "\n;{\nconst sniprun142859_last = (\n"
+            + statement.trim_end_matches(';')
+            + "\n);\nif (sniprun142859_last !== undefined) {\n  console.log(require('util').inspect(sniprun142859_last));\n}\n}\n"
+    }
+}
+
 impl Interpreter for JS_original {
     fn new_with_level(data: DataHolder, level: SupportLevel) -> Box<JS_original> {
         let bwd = data.work_dir.clone() + "/js-original";
@@ -30,6 +144,18 @@ impl Interpreter for JS_original {
         String::from("JS_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("node", &["--version"])]
+    }
+
+    fn has_treesitter_capability() -> bool {
+        true
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_supported_languages() -> Vec<String> {
         vec![
             String::from("JavaScript"),
@@ -45,6 +171,14 @@ impl Interpreter for JS_original {
         self.support_level = level;
     }
 
+    fn get_options() -> Vec<InterpreterOption> {
+        vec![InterpreterOption::new(
+            "print_last_expression",
+            OptionKind::Bool,
+            "print the expression that ends the code, if it is not undefined (default: true)",
+        )]
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -77,6 +211,9 @@ impl Interpreter for JS_original {
     }
 
     fn add_boilerplate(&mut self) -> Result<(), SniprunError> {
+        if self.get_bool_option("print_last_expression", true) {
+            self.code = JS_original::print_last_expression(&self.code);
+        }
         Ok(())
     }
 
@@ -89,10 +226,7 @@ impl Interpreter for JS_original {
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("node")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("node").arg(&self.main_file_path))?;
         info!("yay from js interpreter");
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
@@ -119,4 +253,46 @@ mod test_js_original {
         let string_result = res.unwrap();
         assert_eq!(string_result, "Hello, World!\n");
     }
+
+    #[test]
+    fn timeout() {
+        use crate::job::{Job, Phase};
+        use neovim_lib::Value;
+
+        let mut data = DataHolder::new();
+        data.job = Some(Job::new());
+        data.timeout = 60;
+        // per-interpreter timeout overrides the global one
+        data.interpreter_options = Some(Value::from(vec![(
+            Value::from("JS_original"),
+            Value::from(vec![(Value::from("timeout"), Value::from(1))]),
+        )]));
+        data.current_bloc = String::from("while (true) {}");
+        let mut interpreter = JS_original::new(data);
+        let res = interpreter.run();
+
+        assert_eq!(res, Err(SniprunError::TimeoutError(Phase::Execute, 1)));
+    }
+
+    #[test]
+    fn print_last_expression() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from(
+            "let a = [1, 2]; a = a.map(x => x * 2)\nconsole.log('{')\na\n  .join(';')",
+        );
+        let mut interpreter = JS_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "{\n'2;4'\n");
+
+        // regex literals and block comments may hold quotes, brackets and ';'
+        data.current_bloc = String::from(
+            "let re = /[/;'}]/ /* a } comment; */\n'a;b}'.split(re).length + 'c;d'.split(/;/).length / 1",
+        );
+        let mut interpreter = JS_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "5\n");
+
+        // statements and undefined values are not printed
+        data.current_bloc = String::from("let a = 1\nfunction f() { return a }\nconsole.log(f())");
+        let mut interpreter = JS_original::new(data);
+        assert_eq!(interpreter.run().unwrap(), "1\n");
+    }
 }
@@ -42,6 +42,17 @@ impl Interpreter for Java_original {
         String::from("Java_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![
+            Requirement::required("javac", &["-version"]),
+            Requirement::required("java", &["-version"]),
+        ]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_current_level(&self) -> SupportLevel {
         self.support_level
     }
@@ -99,12 +110,12 @@ impl Interpreter for Java_original {
         write(&self.main_file_path, &self.code).expect("Unable to write to file for java-original");
 
         //compile it (to the bin_path that arleady points to the rigth path)
-        let output = Command::new("javac")
-            .arg("-d")
-            .arg(&self.java_work_dir)
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("javac")
+                .arg("-d")
+                .arg(&self.java_work_dir)
+                .arg(&self.main_file_path),
+        )?;
 
         //TODO if relevant, return the error number (parse it from stderr)
         if !output.status.success() {
@@ -116,12 +127,12 @@ impl Interpreter for Java_original {
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new("java")
-            .arg("-cp")
-            .arg(&self.java_work_dir)
-            .arg(&self.bin_name)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("java")
+                .arg("-cp")
+                .arg(&self.java_work_dir)
+                .arg(&self.bin_name),
+        )?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
@@ -41,6 +41,14 @@ impl Interpreter for Julia_original {
         String::from("Julia_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("julia", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -101,10 +109,7 @@ impl Interpreter for Julia_original {
         Ok(())
     }
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("julia")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("julia").arg(&self.main_file_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
@@ -0,0 +1,232 @@
+#[derive(Clone)]
+#[allow(non_camel_case_types)]
+pub struct Jupyter_generic {
+    support_level: SupportLevel,
+    data: DataHolder,
+    code: String,
+    jupyter_work_dir: String,
+}
+
+impl Jupyter_generic {
+    ///filetype -> kernel name, from the 'kernels' option
+    fn kernels(data: &DataHolder) -> Vec<(String, String)> {
+        let kernels = crate::config::interpreter_option(
+            &data.interpreter_options,
+            &Jupyter_generic::get_name(),
+            "kernels",
+        );
+        let kernels = match kernels.as_ref().and_then(|k| k.as_map()) {
+            Some(kernels) => kernels,
+            None => return vec![],
+        };
+        kernels
+            .iter()
+            .filter_map(|(filetype, kernel)| {
+                Some((filetype.as_str()?.to_string(), kernel.as_str()?.to_string()))
+            })
+            .collect()
+    }
+
+    ///the kernel for the filetype of the code
+    fn kernel(&self) -> Result<String, SniprunError> {
+        Jupyter_generic::kernels(&self.data)
+            .into_iter()
+            .find(|(filetype, _)| filetype == &self.data.filetype)
+            .map(|(_, kernel)| kernel)
+            .ok_or_else(|| {
+                SniprunError::InterpreterLimitationError(format!(
+                    "no jupyter kernel set for the filetype '{}' in the 'kernels' option",
+                    self.data.filetype
+                ))
+            })
+    }
+
+    ///run the code on the kernel, kept alive between runs if `repl`, or started for this run
+    fn run_on_kernel(&self, repl: bool) -> Result<String, SniprunError> {
+        let kernel = self.kernel()?;
+        let mut data = self.data.clone();
+        if !repl {
+            data.interpreter_data = None;
+        }
+        crate::jupyter::run(
+            &data,
+            &Jupyter_generic::get_name(),
+            &kernel,
+            &self.jupyter_work_dir,
+            &self.code,
+            &self.process_settings(),
+        )
+    }
+}
+
+impl Interpreter for Jupyter_generic {
+    fn new_with_level(data: DataHolder, level: SupportLevel) -> Box<Jupyter_generic> {
+        let jwd = data.work_dir.clone() + "/jupyter_generic";
+        let mut builder = DirBuilder::new();
+        builder.recursive(true);
+        builder
+            .create(&jwd)
+            .expect("Could not create directory for jupyter_generic");
+        Box::new(Jupyter_generic {
+            data,
+            support_level: level,
+            code: String::new(),
+            jupyter_work_dir: jwd,
+        })
+    }
+
+    fn get_name() -> String {
+        String::from("Jupyter_generic")
+    }
+
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("jupyter-kernel", &["--version"])]
+    }
+
+    fn get_options() -> Vec<InterpreterOption> {
+        vec![InterpreterOption::new(
+            "kernels",
+            OptionKind::Table,
+            "jupyter kernel to run each filetype on, eg: { r = \"ir\", rust = \"rust\" }",
+        )]
+    }
+
+    fn behave_repl_like_default() -> bool {
+        true
+    }
+
+    fn has_repl_capability() -> bool {
+        true
+    }
+
+    fn get_supported_languages() -> Vec<String> {
+        vec![]
+    }
+
+    fn get_configured_languages(data: &DataHolder) -> Vec<String> {
+        Jupyter_generic::kernels(data)
+            .into_iter()
+            .map(|(filetype, _)| filetype)
+            .collect()
+    }
+
+    fn get_current_level(&self) -> SupportLevel {
+        self.support_level
+    }
+    fn set_current_level(&mut self, level: SupportLevel) {
+        self.support_level = level;
+    }
+
+    fn get_data(&self) -> DataHolder {
+        self.data.clone()
+    }
+
+    fn get_max_support_level() -> SupportLevel {
+        SupportLevel::Bloc
+    }
+
+    fn fetch_code(&mut self) -> Result<(), SniprunError> {
+        if !self
+            .data
+            .current_bloc
+            .replace(&[' ', '\t', '\n', '\r'][..], "")
+            .is_empty()
+            && self.get_current_level() >= SupportLevel::Bloc
+        {
+            self.code = self.data.current_bloc.clone();
+        } else if !self.data.current_line.replace(" ", "").is_empty()
+            && self.get_current_level() >= SupportLevel::Line
+        {
+            self.code = self.data.current_line.clone();
+        } else {
+            self.code = String::from("");
+        }
+        Ok(())
+    }
+
+    fn add_boilerplate(&mut self) -> Result<(), SniprunError> {
+        self.code = unindent(&format!("{}{}", "\n", self.code.as_str()));
+        Ok(())
+    }
+
+    fn build(&mut self) -> Result<(), SniprunError> {
+        Ok(())
+    }
+
+    fn execute(&mut self) -> Result<String, SniprunError> {
+        self.run_on_kernel(false)
+    }
+}
+
+impl ReplLikeInterpreter for Jupyter_generic {
+    fn fetch_code_repl(&mut self) -> Result<(), SniprunError> {
+        self.fetch_code()
+    }
+
+    fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
+        self.add_boilerplate()
+    }
+
+    fn build_repl(&mut self) -> Result<(), SniprunError> {
+        Ok(())
+    }
+
+    fn execute_repl(&mut self) -> Result<String, SniprunError> {
+        self.run_on_kernel(true)
+    }
+}
+
+#[cfg(test)]
+mod test_jupyter_generic {
+    use super::*;
+    use crate::*;
+
+    fn with_kernels(data: &mut DataHolder, kernels: Vec<(&str, &str)>) {
+        let kernels = kernels
+            .into_iter()
+            .map(|(f, k)| (neovim_lib::Value::from(f), neovim_lib::Value::from(k)))
+            .collect::<Vec<_>>();
+        data.interpreter_options = Some(neovim_lib::Value::from(vec![(
+            neovim_lib::Value::from("Jupyter_generic"),
+            neovim_lib::Value::from(vec![(
+                neovim_lib::Value::from("kernels"),
+                neovim_lib::Value::from(kernels),
+            )]),
+        )]));
+    }
+
+    #[test]
+    fn configured_kernels() {
+        let mut data = DataHolder::new();
+        assert!(Jupyter_generic::get_configured_languages(&data).is_empty());
+
+        with_kernels(&mut data, vec![("r", "ir"), ("rust", "rust")]);
+        assert_eq!(
+            Jupyter_generic::get_configured_languages(&data),
+            vec![String::from("r"), String::from("rust")]
+        );
+        data.filetype = String::from("rust");
+        assert_eq!(Jupyter_generic::new(data.clone()).kernel().unwrap(), "rust");
+        data.filetype = String::from("julia");
+        assert!(Jupyter_generic::new(data).kernel().is_err());
+    }
+
+    #[test]
+    #[ignore] // needs jupyter
+    fn keep_state() {
+        let id = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        let mut data = DataHolder::new();
+        with_kernels(&mut data, vec![("python", "python3")]);
+        data.filetype = String::from("python");
+        data.interpreter_data = id;
+
+        data.current_bloc = String::from("a = 41");
+        let mut interpreter = Jupyter_generic::new(data.clone());
+        interpreter.run_at_level_repl(SupportLevel::Bloc).unwrap();
+
+        data.current_bloc = String::from("print(a + 1)");
+        let mut interpreter = Jupyter_generic::new(data);
+        let res = interpreter.run_at_level_repl(SupportLevel::Bloc);
+        assert_eq!(res.unwrap(), "42\n");
+    }
+}
@@ -30,10 +30,22 @@ impl Interpreter for Lua_nvim {
         String::from("Lua_nvim")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("nvim", &["--version"])]
+    }
+
+    fn has_treesitter_capability() -> bool {
+        true
+    }
+
     fn get_supported_languages() -> Vec<String> {
         vec![String::from("Lua"), String::from("lua")]
     }
 
+    fn matches_code(code: &str) -> Option<bool> {
+        Some(crate::detect::uses_vim_api(code))
+    }
+
     fn get_current_level(&self) -> SupportLevel {
         self.support_level
     }
@@ -49,24 +61,6 @@ impl Interpreter for Lua_nvim {
         SupportLevel::Bloc
     }
 
-    fn fallback(&mut self) -> Option<Result<String, SniprunError>> {
-        //do not fallback if one's is explicitely selected
-        if self.support_level == SupportLevel::Selected {
-            return None;
-        }
-
-        self.fetch_code().expect("could not fetch code");
-        if !(self.code.contains("nvim") || self.code.contains("vim")) {
-            //then this is not lua_nvim code but pure lua one
-            let mut good_interpreter = crate::interpreters::Lua_original::new_with_level(
-                self.data.clone(),
-                self.get_current_level(),
-            );
-            return Some(good_interpreter.run());
-        }
-        return None;
-    }
-
     fn fetch_code(&mut self) -> Result<(), SniprunError> {
         if !self
             .data
@@ -111,14 +105,14 @@ impl Interpreter for Lua_nvim {
             }
         } else {
             //else, executing in another nvim instance
-            let output = Command::new("nvim")
-                .arg("--headless")
-                .arg("-c")
-                .arg(format!("luafile {}", &self.main_file_path))
-                .arg("-c")
-                .arg("q!")
-                .output()
-                .expect("Unable to start process");
+            let output = self.run_command(
+                Command::new("nvim")
+                    .arg("--headless")
+                    .arg("-c")
+                    .arg(format!("luafile {}", &self.main_file_path))
+                    .arg("-c")
+                    .arg("q!"),
+            )?;
             info!("yay from lua interpreter - in another nvim instance");
             if output.status.success() {
                 return Ok(String::from_utf8(output.stdout).unwrap());
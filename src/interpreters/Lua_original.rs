@@ -30,10 +30,26 @@ impl Interpreter for Lua_original {
         String::from("Lua_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("lua", &["-v"])]
+    }
+
+    fn has_treesitter_capability() -> bool {
+        true
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_supported_languages() -> Vec<String> {
         vec![String::from("Lua"), String::from("lua")]
     }
 
+    fn matches_code(code: &str) -> Option<bool> {
+        Some(!crate::detect::uses_vim_api(code))
+    }
+
     fn get_current_level(&self) -> SupportLevel {
         self.support_level
     }
@@ -52,23 +68,6 @@ impl Interpreter for Lua_original {
         SupportLevel::Bloc
     }
 
-    fn fallback(&mut self) -> Option<Result<String, SniprunError>> {
-        //do not fallback if one's is explicitely selected
-        if self.support_level == SupportLevel::Selected {
-            return None;
-        }
-        self.fetch_code().expect("could not fetch code");
-        if self.code.contains("nvim") || self.code.contains("vim") {
-            //then this is not pure lua code but  lua-nvim one
-            let mut good_interpreter = crate::interpreters::Lua_nvim::new_with_level(
-                self.data.clone(),
-                self.get_current_level(),
-            );
-            return Some(good_interpreter.run());
-        }
-        return None;
-    }
-
     fn fetch_code(&mut self) -> Result<(), SniprunError> {
         if !self
             .data
@@ -101,10 +100,7 @@ impl Interpreter for Lua_original {
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("lua")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("lua").arg(&self.main_file_path))?;
         info!("yay from lua interpreter");
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
@@ -5,9 +5,7 @@ pub struct Python3_jupyter {
     data: DataHolder,
     code: String,
     imports: String,
-    kernel_file: String,
     main_file_path: String,
-    launcher_path: String,
     plugin_root: String,
     cache_dir: String,
 }
@@ -69,24 +67,6 @@ impl Python3_jupyter {
         }
         return false;
     }
-    // /// In theory, is a good idea, but somehow doesn't work
-    // fn wait_on_kernel(&self) -> Result<(), SniprunError> {
-    //     let step = std::time::Duration::from_millis(100);
-    //     let mut timeout = std::time::Duration::from_millis(15000);
-    //     loop {
-    //         if let Ok(content) = std::fs::read_to_string(&self.kernel_file) {
-    //             if !content.is_empty() {
-    //                 return Ok(());
-    //             }
-    //         }
-    //         std::thread::sleep(step);
-    //         if let Some(remaining) = timeout.checked_sub(step) {
-    //             timeout = remaining;
-    //         } else {
-    //             return Err(SniprunError::CustomError(String::from("Timeout on jupyter kernel start expired")));
-    //         }
-    //     }
-    // }
 }
 
 impl Interpreter for Python3_jupyter {
@@ -101,19 +81,15 @@ impl Interpreter for Python3_jupyter {
 
         //pre-create string pointing to main file's and binary's path
         let mfp = pwd.clone() + "/main.py";
-        let lp = pwd.clone() + "/main.sh";
 
         let pgr = data.sniprun_root_dir.clone();
 
-        let kp = pwd.clone() + "/kernel_sniprun.json";
         Box::new(Python3_jupyter {
             data,
             support_level: level,
             code: String::new(),
             imports: String::new(),
-            kernel_file: kp,
             main_file_path: mfp,
-            launcher_path: lp,
             plugin_root: pgr,
             cache_dir: pwd,
         })
@@ -123,6 +99,13 @@ impl Interpreter for Python3_jupyter {
         String::from("Python3_jupyter")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![
+            Requirement::required("python3", &["--version"]),
+            Requirement::required("jupyter-kernel", &["--version"]),
+        ]
+    }
+
     fn behave_repl_like_default() -> bool {
         true
     }
@@ -197,10 +180,7 @@ impl Interpreter for Python3_jupyter {
         Ok(())
     }
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("python3")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("python3").arg(&self.main_file_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
@@ -217,47 +197,9 @@ impl Interpreter for Python3_jupyter {
 }
 impl ReplLikeInterpreter for Python3_jupyter {
     fn fetch_code_repl(&mut self) -> Result<(), SniprunError> {
-        self.fetch_code()?;
-        let saved_code = self.read_previous_code();
-        let mut saved_code: Vec<_> = saved_code.lines().collect();
-        if saved_code.is_empty() {
-            //initialize kernel. Relying on self.read_previous_code to
-            //know when to start a new kernel is important as
-            //this will be cleared by the SnipReplMemoryClean command
-            let _res = std::fs::remove_file(&self.kernel_file);
-            let _res = Command::new("jupyter-kernel")
-                .arg("--kernel=python3")
-                .arg(String::from("--KernelManager.connection_file=") + &self.kernel_file)
-                .spawn();
-            info!("Initialized kernel");
-        } else {
-            // kernel already running
-            info!(
-                "Using already loaded jupyter kernel at {}",
-                self.kernel_file
-            );
-        }
-        // do not re-import already loaded imports
-        let mut new_imports = String::new();
-        for import in self.imports.lines() {
-            if !saved_code.contains(&import) {
-                saved_code.push(import);
-                new_imports = new_imports + import + "\n";
-                info!("new import found: {}", import);
-            } else {
-                info!("import already loaded: {}", import);
-            }
-        }
-
-        // save kernel + seen_imports in sniprun memory
-        self.save_code(saved_code.join("\n"));
-
-        self.imports = new_imports;
-
-        Ok(())
+        self.fetch_code()
     }
     fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
-        info!("begins add boilerplate repl");
         if !self.imports.is_empty() {
             let mut indented_imports = String::new();
             for import in self.imports.lines() {
@@ -266,87 +208,25 @@ impl ReplLikeInterpreter for Python3_jupyter {
 
             self.imports = String::from("\ntry:\n") + &indented_imports + "\nexcept:\n\tpass\n";
         }
-        //empty print a newline, in case the jupyter prompt interferes.
-        //anyway, removed by sniprun itself before display
-        self.code = self.imports.clone()
-            + "\nprint(\"\")\n"
-            + &unindent(&format!("{}{}", "\n", self.code.as_str()));
-
+        self.code =
+            self.imports.clone() + "\n" + &unindent(&format!("{}{}", "\n", self.code.as_str()));
         Ok(())
     }
 
     fn build_repl(&mut self) -> Result<(), SniprunError> {
-        let actual_command = String::from("echo")
-            + " "
-            + &String::from("'exec(open(\"")
-            + &self.main_file_path
-            + "\").read())"
-            + "' "
-            + "|"
-            + " "
-            + "jupyter-console"
-            + " "
-            + "--existing"
-            + " "
-            + &self.kernel_file.clone()
-            + " "
-            + "--simple-prompt"
-            + " "
-            + "-y"
-            + " "
-            + " --no-confirm"
-            + " "
-            + "--ZMQTerminalInteractiveShell.banner=\"\""
-            + " "
-            + "--Application.log_level=0";
-
-        write(&self.launcher_path, &actual_command)
-            .expect("Unable to write file for python3_jupyter");
-        info!("command written to launcher:\n{}\n", actual_command);
-        write(&self.main_file_path, &self.code)
-            .expect("Unable to write to file for python3_jupyter");
         Ok(())
     }
 
     fn execute_repl(&mut self) -> Result<String, SniprunError> {
-        info!(
-            "json kernel file exists yet? {}",
-            std::path::Path::new(&self.kernel_file).exists()
-        );
-        // self.wait_on_kernel()?;
-
-        let output = Command::new("sh")
-            .arg(&self.launcher_path)
-            .output()
-            .expect("Unable to start process");
-        let result = String::from_utf8(output.stdout).unwrap();
-        let mut cleaned_result: Vec<_> = result.lines().collect();
-
-        info!("result: {:?}", cleaned_result);
-
-        // first and last lines are the [In] x: prompts from jupyter-console
-        cleaned_result.remove(cleaned_result.len() - 1);
-        cleaned_result.remove(1);
-        cleaned_result.remove(0);
-
-        info!("cleaned result: {:?}", cleaned_result);
-        if String::from_utf8(output.stderr.clone()).unwrap().is_empty() {
-            return Ok(cleaned_result.join("\n") + "\n");
-        } else {
-            return Err(SniprunError::RuntimeError(
-                String::from_utf8(strip_ansi_escapes::strip(output.stderr.clone()).unwrap())
-                    .unwrap()
-                    .lines()
-                    .last()
-                    .unwrap_or(
-                        &String::from_utf8(
-                            strip_ansi_escapes::strip(output.stderr.clone()).unwrap(),
-                        )
-                        .unwrap(),
-                    )
-                    .to_owned(),
-            ));
-        }
+        // the kernel lives until the REPL memory is cleared, like the processes of other REPLs
+        crate::jupyter::run(
+            &self.data,
+            &Python3_jupyter::get_name(),
+            "python3",
+            &self.cache_dir,
+            &self.code,
+            &self.process_settings(),
+        )
     }
 }
 
@@ -392,14 +272,9 @@ mod test_python3_jupyter {
     }
 
     #[test]
-    #[cfg_attr(feature = "ignore_in_ci", ignore)]
-    #[ignore] // because we don't want to run this in // with simple_print
+    #[ignore] // needs jupyter
     fn simple_print_repl() {
-        let id = Some(Arc::new(Mutex::new(InterpreterData {
-            owner: String::from(""),
-            content: String::from(""),
-            pid: None,
-        })));
+        let id = Some(Arc::new(Mutex::new(InterpreterData::default())));
 
         let mut data = DataHolder::new();
         data.repl_enabled = vec![String::from("Python3_jupyter")];
@@ -407,17 +282,16 @@ mod test_python3_jupyter {
         data.interpreter_data = id.clone();
         data2.interpreter_data = id;
 
-        data.current_bloc = String::from("a=1");
+        data2.current_bloc = String::from("a=1");
         let mut interpreter = Python3_jupyter::new(data2);
         let _res = interpreter.run_at_level_repl(SupportLevel::Import).unwrap();
 
         data.current_bloc = String::from("print(a)");
         let mut interpreter = Python3_jupyter::new(data);
-        let _res = interpreter.run_at_level_repl(SupportLevel::Import);
+        let res = interpreter.run_at_level_repl(SupportLevel::Import);
 
         // should panic if not an Ok()
-        // but for some reason does not work in test mode
-        // let string_result = res.unwrap();
-        // assert_eq!(string_result, "1\n");
+        let string_result = res.unwrap();
+        assert_eq!(string_result, "1\n");
     }
 }
@@ -5,6 +5,8 @@ pub struct Python3_original {
     data: DataHolder,
     code: String,
     imports: String,
+    ///module-level definitions the code needs, from the rest of the file
+    definitions: String,
     main_file_path: String,
     plugin_root: String,
     cache_dir: String,
@@ -27,11 +29,14 @@ impl Python3_original {
         {
             self.code = self.data.current_bloc.clone();
         }
+        self.fetch_definitions(&contents);
+        // the pulled definitions may need imports too
+        let used_code = self.definitions.clone() + "\n" + &self.code;
         for line in contents.lines() {
             // info!("lines are : {}", line);
             if line.contains("import ") //basic selection
                 && line.trim().chars().next() != Some('#')
-            && Python3_original::module_used(line, &self.code)
+            && Python3_original::module_used(line, &used_code)
             {
                 // embed in try catch blocs in case uneeded module is unavailable
                 self.imports = self.imports.clone() + "\n" + line;
@@ -39,6 +44,240 @@ impl Python3_original {
         }
         Ok(())
     }
+
+    /// File level: pull the module-level def, class and assignment blocks that define
+    /// the names used by the code, and the names they use themselves, in source order
+    fn fetch_definitions(&mut self, contents: &str) {
+        if self.support_level < SupportLevel::File {
+            return;
+        }
+        let blocks = Python3_original::module_blocks(contents);
+        // lines of the selection (0-based), its code is already in self.code
+        let selection =
+            (self.data.range[0] - 1).max(0) as usize..self.data.range[1].max(0) as usize;
+        let selection_start = selection.start;
+        let candidates: Vec<&ModuleBlock> = blocks
+            .iter()
+            .filter(|b| !b.defines.is_empty())
+            .filter(|b| b.end <= selection.start || b.start >= selection.end)
+            .collect();
+
+        let mut needed = Python3_original::identifiers(&self.code);
+        let mut pulled: Vec<&ModuleBlock> = vec![];
+        let mut done: std::collections::HashSet<String> = std::collections::HashSet::new();
+        while let Some(name) = needed.iter().find(|n| !done.contains(*n)).cloned() {
+            done.insert(name.clone());
+            let defining: Vec<&&ModuleBlock> = candidates
+                .iter()
+                .filter(|b| b.defines.contains(&name))
+                .collect();
+            // what is defined before the selection, else the first definition after it
+            let before: Vec<&&ModuleBlock> = defining
+                .iter()
+                .copied()
+                .filter(|b| b.end <= selection_start)
+                .collect();
+            let chosen = if before.is_empty() {
+                defining.into_iter().take(1).collect()
+            } else {
+                before
+            };
+            for block in chosen {
+                if !pulled.iter().any(|b| b.start == block.start) {
+                    needed.extend(Python3_original::identifiers(&block.code));
+                    pulled.push(block);
+                }
+            }
+        }
+        pulled.sort_by_key(|b| b.start);
+        info!("python definitions pulled from file: {}", pulled.len());
+        self.definitions = pulled.iter().map(|b| b.code.clone() + "\n").collect();
+    }
+
+    /// split a python file in its top-level statements (with their decorators, bodies,
+    /// and continuation lines)
+    fn module_blocks(contents: &str) -> Vec<ModuleBlock> {
+        let mut blocks: Vec<ModuleBlock> = vec![];
+        let mut in_string = false;
+        for (i, line) in contents.lines().enumerate() {
+            let continues = in_string
+                || line.trim().is_empty()
+                || line.starts_with(&[' ', '\t', '#', ')', ']', '}'][..])
+                || ["else", "elif ", "except", "finally"]
+                    .iter()
+                    .any(|k| line.starts_with(k))
+                || blocks
+                    .last()
+                    .is_some_and(|b| b.code.lines().last().unwrap_or("").starts_with('@'));
+            // lines inside a multiline string (that may not be indented) belong to the same block
+            if (line.matches("\"\"\"").count() + line.matches("\'\'\'").count()) % 2 == 1 {
+                in_string = !in_string;
+            }
+            match blocks.last_mut() {
+                Some(block) if continues => {
+                    block.code = block.code.clone() + "\n" + line;
+                    block.end = i + 1;
+                }
+                _ => blocks.push(ModuleBlock {
+                    start: i,
+                    end: i + 1,
+                    code: line.to_string(),
+                    defines: vec![],
+                }),
+            }
+        }
+        for block in blocks.iter_mut() {
+            let head = block
+                .code
+                .lines()
+                .find(|l| !l.starts_with('@'))
+                .unwrap_or("");
+            block.defines = Python3_original::defined_names(head);
+            block.code = block.code.trim_end().to_string();
+        }
+        blocks
+    }
+
+    /// names defined by the first line of a top-level statement: def, class, or assignment targets
+    fn defined_names(head: &str) -> Vec<String> {
+        let head = head.trim_start_matches("async ");
+        for keyword in ["def ", "class "].iter() {
+            if let Some(rest) = head.strip_prefix(keyword) {
+                let name: String = rest
+                    .trim_start()
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect();
+                return vec![name];
+            }
+        }
+        // an assignment has an '=' outside of brackets, that is not a comparison
+        let chars: Vec<char> = head.chars().collect();
+        let mut depth = 0;
+        for (i, c) in chars.iter().enumerate() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                '\'' | '"' | '#' => return vec![],
+                '=' if depth == 0 => {
+                    let next = chars.get(i + 1);
+                    let previous = if i > 0 { chars.get(i - 1) } else { None };
+                    if next == Some(&'=')
+                        || matches!(previous, Some('=') | Some('!') | Some('<') | Some('>'))
+                    {
+                        return vec![];
+                    }
+                    let targets: String = chars[..i].iter().collect();
+                    let targets = targets.split(':').next().unwrap_or("");
+                    return targets
+                        .split(',')
+                        .map(|t| {
+                            t.trim_matches(
+                                &[
+                                    ' ', '(', ')', '[', ']', '*', '+', '-', '/', '%', '&', '|', '^',
+                                ][..],
+                            )
+                        })
+                        .filter(|t| Python3_original::is_identifier(t) && !KEYWORDS.contains(t))
+                        .map(String::from)
+                        .collect();
+                }
+                _ => (),
+            }
+        }
+        vec![]
+    }
+
+    fn is_identifier(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    fn identifiers(code: &str) -> std::collections::HashSet<String> {
+        code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|w| Python3_original::is_identifier(w))
+            .map(String::from)
+            .collect()
+    }
+
+    /// like the python REPL, print the value of the expression the code ends with
+    fn print_last_expression(code: &str) -> String {
+        let lines: Vec<&str> = code.lines().collect();
+        // the last statement starts on the last line that is not indented nor a continuation
+        let start = match lines.iter().rposition(|l| {
+            !l.trim().is_empty()
+                && !l.starts_with(&[' ', '\t', '#', ')', ']', '}'][..])
+                && !["else", "elif ", "except", "finally"]
+                    .iter()
+                    .any(|k| l.starts_with(k))
+        }) {
+            Some(start) => start,
+            None => return code.to_string(),
+        };
+        let statement = lines[start..].join("\n");
+
+        // the expression is after the last ';', and can't have a top-level '=' or ':'
+        let chars: Vec<char> = statement.chars().collect();
+        let mut expression_start = 0;
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match quote {
+                Some(_) if c == '\\' => i += 1,
+                Some(q) if c == q => quote = None,
+                Some(_) => (),
+                None => match c {
+                    '\'' | '"' => quote = Some(c),
+                    '#' => {
+                        while i < chars.len() && chars[i] != '\n' {
+                            i += 1;
+                        }
+                    }
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' => depth -= 1,
+                    ';' if depth == 0 => expression_start = i + 1,
+                    ':' if depth == 0 => return code.to_string(),
+                    '=' if depth == 0 => {
+                        let comparison = chars.get(i + 1) == Some(&'=')
+                            || (i > 0 && "=!<>".contains(chars[i - 1]));
+                        if !comparison {
+                            return code.to_string();
+                        }
+                        i += 1;
+                    }
+                    _ => (),
+                },
+            }
+            i += 1;
+        }
+        let expression: String = chars[expression_start..].iter().collect();
+        let first_word = expression
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .find(|w| !w.is_empty())
+            .unwrap_or("");
+        let statements = [
+            "assert", "async", "await", "break", "class", "continue", "def", "del", "for", "from",
+            "global", "if", "import", "nonlocal", "pass", "raise", "return", "try", "while",
+            "with", "yield",
+        ];
+        if expression.trim().is_empty()
+            || expression.trim_start().starts_with('@')
+            || statements.contains(&first_word)
+        {
+            return code.to_string();
+        }
+        let before: String = chars[..expression_start].iter().collect();
+        lines[..start].join("\n")
+            + "\n"
+            + &before
+            + "\nsniprun142859_last = (\n"
+            + expression.trim()
+            + "\n)\nif sniprun142859_last is not None:\n    print(repr(sniprun142859_last))\n"
+    }
+
     fn module_used(line: &str, code: &str) -> bool {
         info!(
             "checking for python module usage: line {} in code {}",
@@ -67,6 +306,21 @@ impl Python3_original {
     }
 }
 
+///A top-level statement of a python file
+struct ModuleBlock {
+    ///lines of the file (0-based, end excluded) it spans
+    start: usize,
+    end: usize,
+    code: String,
+    ///the names it defines: def, class or assignment targets
+    defines: Vec<String>,
+}
+
+const KEYWORDS: [&str; 12] = [
+    "if", "for", "while", "with", "return", "print", "assert", "del", "global", "lambda", "not",
+    "yield",
+];
+
 impl Interpreter for Python3_original {
     fn new_with_level(data: DataHolder, level: SupportLevel) -> Box<Python3_original> {
         //create a subfolder in the cache folder
@@ -86,6 +340,7 @@ impl Interpreter for Python3_original {
             support_level: level,
             code: String::from(""),
             imports: String::from(""),
+            definitions: String::from(""),
             main_file_path: mfp,
             plugin_root: pgr,
             cache_dir: rwd,
@@ -96,6 +351,18 @@ impl Interpreter for Python3_original {
         String::from("Python3_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("python3", &["--version"])]
+    }
+
+    fn has_treesitter_capability() -> bool {
+        true
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn behave_repl_like_default() -> bool {
         false
     }
@@ -103,6 +370,14 @@ impl Interpreter for Python3_original {
         true
     }
 
+    fn get_options() -> Vec<InterpreterOption> {
+        vec![InterpreterOption::new(
+            "print_last_expression",
+            OptionKind::Bool,
+            "print the repr() of the expression that ends the code, if it is not None (default: true)",
+        )]
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -128,7 +403,7 @@ impl Interpreter for Python3_original {
     }
 
     fn get_max_support_level() -> SupportLevel {
-        SupportLevel::Import
+        SupportLevel::File
     }
 
     fn fetch_code(&mut self) -> Result<(), SniprunError> {
@@ -160,7 +435,11 @@ impl Interpreter for Python3_original {
 
             self.imports = String::from("\ntry:\n") + &indented_imports + "\nexcept:\n\tpass\n";
         }
-        self.code = self.imports.clone() + &unindent(&format!("{}{}", "\n", self.code.as_str()));
+        let mut code = unindent(&format!("{}{}", "\n", self.code.as_str()));
+        if self.get_bool_option("print_last_expression", true) {
+            code = Python3_original::print_last_expression(&code);
+        }
+        self.code = self.imports.clone() + "\n" + &self.definitions + &code;
         Ok(())
     }
     fn build(&mut self) -> Result<(), SniprunError> {
@@ -170,10 +449,7 @@ impl Interpreter for Python3_original {
         Ok(())
     }
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("python3")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("python3").arg(&self.main_file_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
@@ -193,52 +469,85 @@ impl ReplLikeInterpreter for Python3_original {
         self.fetch_code()
     }
     fn build_repl(&mut self) -> Result<(), SniprunError> {
-        self.build()
+        Ok(())
     }
 
     fn execute_repl(&mut self) -> Result<String, SniprunError> {
-        self.execute()
-    }
-    fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
-        info!("begins add boilerplate repl");
-        //load save & load functions
-        let mut path_to_python_functions = self.plugin_root.clone();
-        path_to_python_functions.push_str("/src/interpreters/Python3_original/saveload.py");
-        let python_functions = std::fs::read_to_string(&path_to_python_functions).unwrap();
-        let klepto_memo = String::from("'") + &self.cache_dir.clone() + "/" + "memo" + "'";
-
-        let mut final_code = self.imports.clone();
-        final_code.push_str("\n");
-        final_code.push_str(&python_functions);
-        final_code.push_str("\n");
-        if self.read_previous_code().is_empty() {
-            //first run
-            self.save_code("Not the first run anymore".to_string());
+        // the code runs in a python process that stays alive between runs (see repl.py), so
+        // everything it defines is kept as is, even what can't be pickled
+        let settings = self.process_settings();
+        let driver = self.plugin_root.clone() + "/src/interpreters/Python3_original/repl.py";
+        let process = crate::repl::process(
+            &self.data,
+            &Python3_original::get_name(),
+            "python3",
+            || {
+                let mut python = Command::new("python3");
+                python.arg("-u").arg(&driver);
+                python
+            },
+            &settings,
+        )?;
+        // the session is read and written before and after the run, never while holding the process
+        let pid = process.lock().unwrap().id();
+        self.set_pid(pid);
+        let restored = crate::repl::restored_code(&self.data, &Python3_original::get_name());
+
+        let stdin = if self.data.stdin.is_empty() {
+            String::from("/dev/null")
         } else {
-            //not the first run, should load old variables
-            {
-                final_code.push_str("sniprun142859_load(");
-                final_code.push_str(&klepto_memo);
-                final_code.push_str(")");
+            let input = self
+                .data
+                .stdin
+                .parse::<crate::stdin::StdinSource>()?
+                .read(&self.data)?;
+            let path = self.cache_dir.clone() + "/stdin";
+            write(&path, input).expect("Unable to write stdin file for python3_original");
+            path
+        };
+        let output = {
+            let mut process = process.lock().unwrap();
+            if let Some(code) = restored {
+                info!("running again the code of the restored session");
+                let _ = self.send(&mut process, &code, "/dev/null");
             }
-            final_code.push_str("\n");
+            self.send(&mut process, &self.code, &stdin)?
+        };
+        if output.status.success() {
+            // to run it again when the session is restored
+            self.save_code(self.code.clone());
+            Ok(String::from_utf8(output.stdout).unwrap())
+        } else {
+            // the whole traceback, repl.py already left out its own frame
+            Err(SniprunError::RuntimeError(
+                String::from_utf8(output.stderr).unwrap(),
+            ))
         }
-
-        final_code.push_str(&unindent(&format!("{}{}", "\n", self.code.as_str())));
-        final_code.push_str("\n");
-        {
-            final_code.push_str("sniprun142859_save("); // if the run has not failed, save new variables
-            final_code.push_str(&klepto_memo);
-            final_code.push_str(")");
+    }
+    fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
+        let mut code = unindent(&format!("{}{}", "\n", self.code.as_str()));
+        if self.get_bool_option("print_last_expression", true) {
+            code = Python3_original::print_last_expression(&code);
         }
-
-        self.code = final_code.clone();
-        // info!("---{}---", &final_code);
-
+        self.code = self.imports.clone() + "\n" + &code + "\n";
         Ok(())
     }
 }
 
+impl Python3_original {
+    ///run the code in the REPL process (see repl.py), reading the stdin file
+    fn send(
+        &self,
+        process: &mut crate::repl::ReplProcess,
+        code: &str,
+        stdin: &str,
+    ) -> Result<std::process::Output, SniprunError> {
+        let marker = process.new_marker();
+        let request = format!("{} {} {}\n{}", marker, code.len(), stdin, code);
+        process.send(&self.data, &request, &marker, &self.process_settings())
+    }
+}
+
 #[cfg(test)]
 mod test_python3_original {
     use super::*;
@@ -248,6 +557,10 @@ mod test_python3_original {
         simple_print();
         print_quote();
         get_import();
+        read_stdin();
+        get_definitions();
+        print_last_expression();
+        repl_state();
     }
     fn simple_print() {
         let mut data = DataHolder::new();
@@ -270,7 +583,17 @@ mod test_python3_original {
         assert_eq!(string_result, "->\" 1\n");
     }
 
-    fn get_import(){
+    fn read_stdin() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("print(input() + input())");
+        data.stdin = String::from("text:sn\\nip");
+        let mut interpreter = Python3_original::new(data);
+        let res = interpreter.run();
+
+        assert_eq!(res.unwrap(), "snip\n");
+    }
+
+    fn get_import() {
         let mut data = DataHolder::new();
         data.current_bloc = String::from("print(cos(0))");
 
@@ -289,4 +612,91 @@ mod test_python3_original {
         std::fs::remove_file(dfpc).unwrap();
     }
 
+    fn get_definitions() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("print(area(2))");
+        data.range = [10, 10];
+
+        data.filepath = String::from("ressources/definitions.py");
+        let dfpc = data.filepath.clone();
+        let mut file = File::create(&data.filepath).unwrap();
+        file.write_all(
+            b"from math import pi\n\
+            import os\n\
+            \n\
+            SCALE = 2\n\
+            UNUSED = os.getcwd()\n\
+            \n\
+            def area(r):\n    return round(pi * square(r)) * SCALE\n\
+            \n\
+            print(area(2))\n\
+            def square(x):\n    return x * x\n",
+        )
+        .unwrap();
+
+        let mut interpreter = Python3_original::new(data);
+        let res = interpreter.run_at_level(SupportLevel::File);
+
+        // should panic if not an Ok()
+        let string_result = res.unwrap();
+        assert_eq!(string_result, "26\n");
+        assert!(!interpreter.code.contains("UNUSED"));
+
+        std::fs::remove_file(dfpc).unwrap();
+    }
+
+    fn print_last_expression() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("a = 2\nif a:\n    b = 'x' * a\nprint(1); a * 21\nb");
+        let mut interpreter = Python3_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "1\n'xx'\n");
+
+        // statements and None values are not printed
+        data.current_bloc = String::from("a = [3]\na.append(4)\nfor i in a:\n    i");
+        let mut interpreter = Python3_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "");
+
+        data.current_bloc = String::from("1 + 1");
+        data.interpreter_options = Some(neovim_lib::Value::Map(vec![(
+            neovim_lib::Value::from("Python3_original"),
+            neovim_lib::Value::Map(vec![(
+                neovim_lib::Value::from("print_last_expression"),
+                neovim_lib::Value::from(false),
+            )]),
+        )]));
+        let mut interpreter = Python3_original::new(data);
+        assert_eq!(interpreter.run().unwrap(), "");
+    }
+
+    fn repl_state() {
+        let mut data = DataHolder::new();
+        data.sniprun_root_dir = std::env::current_dir().unwrap().display().to_string();
+        data.interpreter_data = Some(std::sync::Arc::new(std::sync::Mutex::new(
+            crate::InterpreterData::default(),
+        )));
+        data.repl_enabled = vec![String::from("Python3_original")];
+        let run = |code: &str, data: &mut DataHolder| {
+            data.current_bloc = String::from(code);
+            Python3_original::new(data.clone()).run()
+        };
+
+        // open files and generators can't be pickled, but are kept in the process
+        let code = "f = open('/dev/null')\ngen = (i for i in range(3))";
+        assert_eq!(run(code, &mut data), Ok(String::new()));
+        assert_eq!(
+            run("next(gen), f.closed", &mut data),
+            Ok(String::from("(0, False)\n"))
+        );
+
+        data.stdin = String::from("text:hello");
+        assert_eq!(run("input()", &mut data), Ok(String::from("'hello'\n")));
+        let traceback = match run("next(gen) / 0", &mut data) {
+            Err(SniprunError::RuntimeError(traceback)) => traceback,
+            other => panic!("expected a runtime error, got {:?}", other),
+        };
+        assert!(traceback.starts_with("Traceback (most recent call last):\n"));
+        assert!(traceback.contains("File \"<sniprun>\", line "));
+        assert!(traceback.ends_with("ZeroDivisionError: division by zero\n"));
+        assert!(!traceback.contains("sniprun142859_run"));
+    }
 }
@@ -0,0 +1,50 @@
+# REPL of Python3_original: runs the code sniprun sends, always in the same namespace.
+# A request is a line '<marker> <size of the code in bytes> <path of the code's stdin>'
+# followed by the code; once it ran, '<marker> <exit status>' is printed on stdout and
+# stderr, after what the code printed and the traceback of its exception if it raised one.
+import io
+import os
+import sys
+import traceback
+
+
+def sniprun142859_run(code, namespace):
+    try:
+        exec(compile(code, "<sniprun>", "exec"), namespace)
+        return 0
+    except SystemExit as e:
+        if e.code is None or isinstance(e.code, int):
+            return e.code or 0
+        print(e.code, file=sys.stderr)
+        return 1
+    except BaseException:
+        kind, value, tb = sys.exc_info()
+        # the frame of this function is not the user's business
+        traceback.print_exception(kind, value, tb.tb_next)
+        return 1
+
+
+def sniprun142859_main():
+    # requests come from a copy of the standard input, the code gets its own one
+    requests = os.fdopen(os.dup(0), "rb")
+    namespace = {"__name__": "__main__", "__builtins__": __builtins__}
+    while True:
+        header = requests.readline()
+        if not header:
+            break
+        marker, size, stdin_path = header.decode().rstrip("\n").split(" ", 2)
+        code = requests.read(int(size)).decode()
+
+        stdin = os.open(stdin_path, os.O_RDONLY)
+        os.dup2(stdin, 0)
+        os.close(stdin)
+        sys.stdin = io.TextIOWrapper(io.open(0, "rb", closefd=False))
+
+        status = sniprun142859_run(code, namespace)
+        for stream in (sys.stdout, sys.stderr):
+            stream.flush()
+            stream.write("%s %d\n" % (marker, status))
+            stream.flush()
+
+
+sniprun142859_main()
@@ -4,7 +4,6 @@ pub struct R_original {
     support_level: SupportLevel,
     data: DataHolder,
     code: String,
-    r_work_dir: String,
     main_file_path: String,
 }
 impl Interpreter for R_original {
@@ -20,7 +19,6 @@ impl Interpreter for R_original {
             data,
             support_level: level,
             code: String::from(""),
-            r_work_dir: bwd,
             main_file_path: mfp,
         })
     }
@@ -29,6 +27,14 @@ impl Interpreter for R_original {
         String::from("R_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("Rscript", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_supported_languages() -> Vec<String> {
         vec![String::from("R"), String::from("r")]
     }
@@ -90,10 +96,7 @@ impl Interpreter for R_original {
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("Rscript")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("Rscript").arg(&self.main_file_path))?;
         info!("yay from R interpreter");
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
@@ -112,54 +115,135 @@ impl ReplLikeInterpreter for R_original {
         self.build()
     }
     fn execute_repl(&mut self) -> Result<String, SniprunError> {
-        self.execute()
-    }
-
-    fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
-        info!("repl mode");
-        let mut final_code = String::new();
+        // the code runs in an R process that stays alive between runs (see repl.R), so the
+        // packages, data and graphics devices are loaded or opened only once
+        let settings = self.process_settings();
+        let driver = self.data.sniprun_root_dir.clone() + "/src/interpreters/R_original/repl.R";
+        let fifo = self.stdin_fifo();
+        let process = crate::repl::process(
+            &self.data,
+            &R_original::get_name(),
+            "Rscript",
+            || {
+                let _ = std::fs::remove_file(&fifo);
+                let _ = Command::new("mkfifo").arg(&fifo).status();
+                // the requests go to the fd 3, and the stdin of the code is the fifo, opened
+                // without waiting for a writer
+                let mut r = Command::new("sh");
+                r.arg("-c")
+                    .arg(
+                        "[ -p \"$1\" ] || { echo \"$1 is not a fifo\" >&2; exit 1; }
+                         exec Rscript --no-save --no-restore \"$0\" 3<&0 4<>\"$1\" 0<\"$1\" 4<&-",
+                    )
+                    .arg(&driver)
+                    .arg(&fifo);
+                r
+            },
+            &settings,
+        )?;
+        // no locking of the interpreter data while the process is locked
+        let pid = process.lock().unwrap().id();
+        self.set_pid(pid);
+        let restored = crate::repl::restored_code(&self.data, &R_original::get_name());
 
-        let rdata_path = self.r_work_dir.clone() + "/sniprun.RData";
-
-        if self.read_previous_code().is_empty() {
-            //first run
-            self.save_code(String::from("Not the first R run anymore"));
+        let stdin = if self.data.stdin.is_empty() {
+            None
         } else {
-            // not first run, tell R to load old variables
-            {
-                &final_code.push_str("load('");
-                &final_code.push_str(&rdata_path);
-                &final_code.push_str("')");
+            let input = self
+                .data
+                .stdin
+                .parse::<crate::stdin::StdinSource>()?
+                .read(&self.data)?;
+            Some(input)
+        };
+        let output = {
+            let mut process = process.lock().unwrap();
+            if let Some(code) = restored {
+                info!("running again the code of the restored session");
+                let restored_file = self.main_file_path.clone() + ".restored";
+                write(&restored_file, code).expect("Unable to write to file for R-original");
+                let _ = self.send(&mut process, &restored_file);
+            }
+            if let Some(input) = stdin {
+                self.write_stdin(input)?;
             }
+            self.send(&mut process, &self.main_file_path)?
+        };
+        if output.status.success() {
+            // to run it again when the session is restored
+            self.save_code(self.code.clone());
+            Ok(String::from_utf8(output.stdout).unwrap())
+        } else {
+            Err(SniprunError::RuntimeError(
+                String::from_utf8(output.stderr).unwrap(),
+            ))
         }
-        &final_code.push_str("\n");
-        &final_code.push_str(&self.code);
-        &final_code.push_str("\n");
+    }
 
-        {
-            //save state
-            &final_code.push_str("save.image('");
-            &final_code.push_str(&rdata_path);
-            &final_code.push_str("')");
+    fn add_boilerplate_repl(&mut self) -> Result<(), SniprunError> {
+        self.add_boilerplate()
+    }
+}
+
+impl R_original {
+    ///the fifo the REPL process of the session reads as its stdin
+    fn stdin_fifo(&self) -> String {
+        let key = crate::repl::SessionKey::new(&self.data, &R_original::get_name());
+        match key.buffer {
+            Some(buffer) => format!("{}/R-original/stdin_{}", self.data.work_dir, buffer),
+            None => format!("{}/R-original/stdin", self.data.work_dir),
         }
-        self.code = final_code;
+    }
+
+    ///Write the input to the stdin fifo of the REPL process, in the background as the code
+    ///reads it. The code gets an end of file once it is all written, and repl.R drains what it
+    ///left so that the next run does not get it
+    fn write_stdin(&self, input: Vec<u8>) -> Result<(), SniprunError> {
+        let fifo = self.stdin_fifo();
+        let open = |options: &mut std::fs::OpenOptions| {
+            options
+                .open(&fifo)
+                .map_err(|e| SniprunError::InternalError(format!("Could not open {}: {}", fifo, e)))
+        };
+        // holding it for reading too, until the writer is open, so that opening it does not
+        // wait for the process; without it, writing fails once the process is gone
+        let reader = open(std::fs::OpenOptions::new().read(true).write(true))?;
+        let mut writer = open(std::fs::OpenOptions::new().write(true))?;
+        drop(reader);
+        std::thread::spawn(move || {
+            let _ = writer.write_all(&input);
+        });
         Ok(())
     }
+
+    ///run the code of the file in the REPL process (see repl.R)
+    fn send(
+        &self,
+        process: &mut crate::repl::ReplProcess,
+        file: &str,
+    ) -> Result<std::process::Output, SniprunError> {
+        let marker = process.new_marker();
+        let request = format!("{} {}\n", marker, file);
+        process.send(&self.data, &request, &marker, &self.process_settings())
+    }
 }
 
 #[cfg(test)]
 mod test_r_original {
     use super::*;
+    use crate::InterpreterData;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn run_all() {
         //nececssary to run sequentially
         //because of file access & shared things
         simple_print();
-        //should test memory as well
+        repl_state();
     }
     fn simple_print() {
         let mut data = DataHolder::new();
+        data.sniprun_root_dir = std::env::current_dir().unwrap().display().to_string();
         data.current_bloc = String::from("print(\"Hi\");");
         let mut interpreter = R_original::new(data);
         let res = interpreter.run();
@@ -168,4 +252,38 @@ mod test_r_original {
         let string_result = res.unwrap();
         assert!(string_result.contains("Hi"));
     }
+
+    fn repl_state() {
+        let mut data = DataHolder::new();
+        data.sniprun_root_dir = std::env::current_dir().unwrap().display().to_string();
+        data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        data.current_bloc = String::from("a <- 1:3\nf <- function(x) x * 2");
+        let mut interpreter = R_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "");
+        let pid = interpreter.get_pid().unwrap();
+
+        // the visible value is printed, like in the console
+        data.current_bloc = String::from("sum(f(a))");
+        let mut interpreter = R_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "[1] 12\n");
+        assert_eq!(interpreter.get_pid(), Some(pid));
+
+        // an error does not stop the process
+        data.current_bloc = String::from("stop(\"oops\")");
+        let mut interpreter = R_original::new(data.clone());
+        assert!(interpreter.run().unwrap_err().to_string().contains("oops"));
+        data.current_bloc = String::from("cat(length(a))");
+        let mut interpreter = R_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "3");
+
+        // the code reads its own stdin, what it left is not given to the next run
+        data.stdin = String::from("text:sn\nip\nleft");
+        data.current_bloc = String::from("cat(readLines(file(\"stdin\"), n = 2), sep = \"\")");
+        let mut interpreter = R_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "snip");
+        data.stdin = String::new();
+        data.current_bloc = String::from("length(readLines(file(\"stdin\")))");
+        let mut interpreter = R_original::new(data);
+        assert_eq!(interpreter.run().unwrap(), "[1] 0\n");
+    }
 }
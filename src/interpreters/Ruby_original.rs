@@ -30,6 +30,14 @@ impl Interpreter for Ruby_original {
         String::from("Ruby_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![Requirement::required("ruby", &["--version"])]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn get_supported_languages() -> Vec<String> {
         vec![
             String::from("Ruby"),
@@ -89,10 +97,7 @@ impl Interpreter for Ruby_original {
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
-        let output = Command::new("ruby")
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(Command::new("ruby").arg(&self.main_file_path))?;
         info!("yay from ruby interpreter");
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
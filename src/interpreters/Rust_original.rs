@@ -4,6 +4,10 @@ pub struct Rust_original {
     support_level: SupportLevel,
     data: DataHolder,
     code: String,
+    ///items of the file the code needs, put outside of main
+    items: String,
+    ///glob imports of the file, only added if the code doesn't compile without them
+    glob_imports: String,
 
     ///specific to rust
     rust_work_dir: String,
@@ -11,6 +15,639 @@ pub struct Rust_original {
     main_file_path: String,
 }
 impl ReplLikeInterpreter for Rust_original {}
+
+///A top-level item of a rust file, with its attributes and doc comments
+struct RustItem {
+    ///lines of the file (0-based, end excluded) it spans
+    start: usize,
+    end: usize,
+    code: String,
+    ///names it defines (the imported ones for a `use`)
+    defines: Vec<String>,
+    ///for an impl block, the type it is implemented on
+    impl_for: Option<String>,
+}
+
+impl Rust_original {
+    /// File level: collect the use declarations, functions, types, impls, consts and macros
+    /// of the file that the code needs (transitively), in source order
+    fn fetch_items(&mut self) -> std::io::Result<()> {
+        self.items = String::new();
+        self.glob_imports = String::new();
+        if self.support_level < SupportLevel::File || self.data.filepath.is_empty() {
+            return Ok(());
+        }
+        let contents = std::fs::read_to_string(&self.data.filepath)?;
+        let selection =
+            (self.data.range[0] - 1).max(0) as usize..self.data.range[1].max(0) as usize;
+        let items: Vec<RustItem> = Rust_original::top_level_items(&contents)
+            .into_iter()
+            .filter(|i| i.end <= selection.start || i.start >= selection.end)
+            .filter(|i| i.defines != ["main"])
+            .filter(|i| !Rust_original::is_crate_use(i))
+            .collect();
+        // glob imports may bring anything in scope, but also clash with the code's names
+        let (globs, items): (Vec<RustItem>, Vec<RustItem>) = items
+            .into_iter()
+            .partition(|i| i.defines.iter().any(|d| d == "*"));
+        self.glob_imports = globs.iter().map(|i| i.code.clone() + "\n").collect();
+
+        let mut needed = Rust_original::identifiers(&self.code);
+        let mut done = std::collections::HashSet::new();
+        let mut pulled: Vec<&RustItem> = vec![];
+        while let Some(name) = needed.iter().find(|n| !done.contains(*n)).cloned() {
+            done.insert(name.clone());
+            for item in items.iter() {
+                let wanted = item.defines.contains(&name) || item.impl_for.as_ref() == Some(&name);
+                if wanted && !pulled.iter().any(|i| i.start == item.start) {
+                    needed.extend(Rust_original::identifiers(&item.code));
+                    pulled.push(item);
+                }
+            }
+        }
+        pulled.sort_by_key(|i| i.start);
+        info!("rust items pulled from file: {}", pulled.len());
+        self.items = pulled.iter().map(|i| i.code.clone() + "\n").collect();
+        Ok(())
+    }
+
+    /// a `use` of the crate's own modules, that can't resolve outside of the crate
+    fn is_crate_use(item: &RustItem) -> bool {
+        let head = item
+            .code
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.starts_with('#') && !l.starts_with("//"))
+            .unwrap_or("");
+        let mut words = head.split_whitespace().skip_while(|w| w.starts_with("pub"));
+        words.next() == Some("use")
+            && words.next().is_some_and(|path| {
+                ["crate::", "super::", "self::"]
+                    .iter()
+                    .any(|p| path.starts_with(p))
+            })
+    }
+
+    /// Import level: the [dependencies] of the nearest Cargo.toml (with its directory),
+    /// if there are any, so the code can use the crates of the project
+    fn project_dependencies(&self) -> Option<(std::path::PathBuf, String)> {
+        if self.support_level < SupportLevel::Import || self.data.filepath.is_empty() {
+            return None;
+        }
+        let manifest = crate::project::nearest(&self.data.filepath, "Cargo.toml")?;
+        let dir = manifest.parent()?.to_path_buf();
+        let manifest = Rust_original::read_manifest(&manifest)?;
+        // what the members of a workspace inherit from it
+        let workspace = Rust_original::workspace_root(&dir);
+        let inherited = |table: &str| {
+            let (_, workspace) = workspace.as_ref()?;
+            workspace.get(table)?.as_table().cloned()
+        };
+
+        // cargo's default, when the manifest doesn't say
+        let edition = match manifest.get("package").and_then(|p| p.get("edition")) {
+            Some(edition) if Rust_original::is_inherited(edition) => {
+                inherited("package")?.get("edition")?.clone()
+            }
+            Some(edition) => edition.clone(),
+            None => toml::Value::from("2015"),
+        };
+
+        let workspace_dependencies = inherited("dependencies").unwrap_or_default();
+        let mut dependencies = toml::value::Table::new();
+        let project_dependencies = manifest.get("dependencies").and_then(|d| d.as_table());
+        for (name, spec) in project_dependencies.into_iter().flatten() {
+            let spec = if Rust_original::is_inherited(spec) {
+                match workspace_dependencies.get(name) {
+                    Some(inherited) => {
+                        let (root, _) = workspace.as_ref()?;
+                        let inherited = Rust_original::absolute_path(inherited, root);
+                        Rust_original::merge_inherited(inherited, spec)
+                    }
+                    None => {
+                        info!("dependency {} is not in the workspace, skipped", name);
+                        continue;
+                    }
+                }
+            } else {
+                Rust_original::absolute_path(spec, &dir)
+            };
+            dependencies.insert(name.clone(), spec.into());
+        }
+        if dependencies.is_empty() {
+            return None;
+        }
+        let mut package = toml::value::Table::new();
+        package.insert("name".into(), "sniprun_snippet".into());
+        package.insert("version".into(), "0.1.0".into());
+        package.insert("edition".into(), edition);
+        let mut manifest = toml::value::Table::new();
+        manifest.insert("package".into(), package.into());
+        manifest.insert("workspace".into(), toml::value::Table::new().into());
+        manifest.insert("dependencies".into(), dependencies.into());
+        Some((dir, toml::to_string(&manifest).ok()?))
+    }
+
+    /// the parsed Cargo.toml, None if it can't be read or isn't valid toml
+    fn read_manifest(path: &std::path::Path) -> Option<toml::value::Table> {
+        toml::from_str(&std::fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// the directory and [workspace] table of the workspace the package in `dir` belongs to
+    fn workspace_root(dir: &std::path::Path) -> Option<(std::path::PathBuf, toml::value::Table)> {
+        dir.ancestors().find_map(|d| {
+            let mut manifest = Rust_original::read_manifest(&d.join("Cargo.toml"))?;
+            match manifest.remove("workspace")? {
+                toml::Value::Table(workspace) => Some((d.to_path_buf(), workspace)),
+                _ => None,
+            }
+        })
+    }
+
+    /// whether the value is `{ workspace = true, ... }`, inherited from the workspace
+    fn is_inherited(value: &toml::Value) -> bool {
+        value.get("workspace").and_then(|w| w.as_bool()) == Some(true)
+    }
+
+    /// the dependency as a table, `{ version = ... }` for a plain version,
+    /// with its `path` relative to `dir` made absolute
+    fn absolute_path(spec: &toml::Value, dir: &std::path::Path) -> toml::value::Table {
+        let mut spec = match spec {
+            toml::Value::Table(spec) => spec.clone(),
+            version => std::iter::once(("version".to_string(), version.clone())).collect(),
+        };
+        if let Some(path) = spec.get("path").and_then(|p| p.as_str()) {
+            let path = dir.join(path).display().to_string();
+            spec.insert("path".into(), path.into());
+        }
+        spec
+    }
+
+    /// the workspace's dependency, with the fields the member adds (features, optional...)
+    fn merge_inherited(
+        mut inherited: toml::value::Table,
+        member: &toml::Value,
+    ) -> toml::value::Table {
+        for (key, value) in member.as_table().into_iter().flatten() {
+            if key != "workspace" {
+                inherited.insert(key.clone(), value.clone());
+            }
+        }
+        inherited
+    }
+
+    /// make the `path = "..."` (or `directory = "..."`) of a cargo config relative to `dir` absolute
+    fn absolute_paths(config: &str, dir: &std::path::Path) -> String {
+        let re = regex::Regex::new(r#"(path|directory)\s*=\s*"([^"]*)""#).unwrap();
+        re.replace_all(config, |caps: &regex::Captures| {
+            let path = dir.join(&caps[2]);
+            format!("{} = \"{}\"", &caps[1], path.display())
+        })
+        .to_string()
+    }
+
+    /// build in the persistent cargo project, whose target dir is kept between runs
+    /// so the dependencies only compile once
+    fn build_with_cargo(
+        &mut self,
+        dir: &std::path::Path,
+        manifest: &str,
+    ) -> Result<(), SniprunError> {
+        let project = std::path::Path::new(&self.rust_work_dir).join("cargo_project");
+        DirBuilder::new()
+            .recursive(true)
+            .create(project.join("src"))
+            .expect("Could not create directory for rust-original's cargo project");
+        // don't touch an unchanged manifest, or cargo would check the dependencies again
+        let manifest_path = project.join("Cargo.toml");
+        if std::fs::read_to_string(&manifest_path).ok().as_deref() != Some(manifest) {
+            write(&manifest_path, manifest).expect("Unable to write Cargo.toml for rust-original");
+        }
+        // the project's cargo config, for vendored sources or a local registry
+        DirBuilder::new()
+            .recursive(true)
+            .create(project.join(".cargo"))
+            .expect("Could not create directory for rust-original's cargo project");
+        let _ = std::fs::remove_file(project.join(".cargo/config.toml"));
+        for name in [".cargo/config.toml", ".cargo/config"].iter() {
+            if let Ok(config) = std::fs::read_to_string(dir.join(name)) {
+                let config = Rust_original::absolute_paths(&config, dir);
+                write(project.join(".cargo/config.toml"), config)
+                    .expect("Unable to write cargo config for rust-original");
+                break;
+            }
+        }
+        write(project.join("src/main.rs"), &self.code)
+            .expect("Unable to write to file for rust-original");
+
+        let mut command = Command::new("cargo");
+        command
+            .arg("build")
+            .arg("--release")
+            .arg("--quiet")
+            .arg("--target-dir")
+            .arg(project.join("target"))
+            .arg("--manifest-path")
+            .arg(&manifest_path);
+        // cargo looks for its config from the working directory, not from the manifest's
+        let config_path = project.join(".cargo/config.toml");
+        if config_path.exists() {
+            command.arg("--config").arg(&config_path);
+        }
+        if self.get_bool_option("offline", false) {
+            command.arg("--offline");
+        }
+        let output = self.run_command(&mut command)?;
+        if !output.status.success() {
+            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
+            let first_error = error_message
+                .lines()
+                .find(|l| l.starts_with("error"))
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+                .trim_start_matches("error");
+            // cargo stopped before compiling the code: the dependencies are the problem
+            if !error_message.contains("could not compile `sniprun_snippet`") {
+                return Err(SniprunError::InterpreterLimitationError(format!(
+                    "could not build the project's dependencies: {}",
+                    first_error
+                )));
+            }
+            return Err(SniprunError::CompilationError(first_error.to_owned()));
+        }
+        self.bin_path = project
+            .join("target/release/sniprun_snippet")
+            .to_string_lossy()
+            .to_string();
+        self.add_artifact(&self.bin_path);
+        Ok(())
+    }
+
+    /// compile the code, with the project's crates if there are any
+    fn compile(&mut self) -> Result<(), SniprunError> {
+        if let Some((dir, manifest)) = self.project_dependencies() {
+            info!("building with the dependencies of {}", dir.display());
+            match self.build_with_cargo(&dir, &manifest) {
+                // the code may not need them, rustc alone can tell
+                Err(SniprunError::InterpreterLimitationError(e)) => {
+                    info!("building without the project's crates: {}", e)
+                }
+                result => return result,
+            }
+        }
+
+        //write code to file
+        let mut _file =
+            File::create(&self.main_file_path).expect("Failed to create file for rust-original");
+        write(&self.main_file_path, &self.code).expect("Unable to write to file for rust-original");
+
+        //compile it (to the bin_path that arleady points to the rigth path)
+        let output = self.run_command(
+            Command::new("rustc")
+                .arg("-O")
+                .arg("--out-dir")
+                .arg(&self.rust_work_dir)
+                .arg(&self.main_file_path),
+        )?;
+
+        //TODO if relevant, return the error number (parse it from stderr)
+        if !output.status.success() {
+            let error_message = String::from_utf8(output.stderr).unwrap();
+            //
+            //take first line and remove first 'error' word (redondant)
+            let first_line = error_message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+                .trim_start_matches("error");
+            return Err(SniprunError::CompilationError(first_line.to_owned()));
+        } else {
+            self.add_artifact(&self.bin_path);
+            return Ok(());
+        }
+    }
+
+    /// like a REPL, print the value of the expression the code ends with (without a ';')
+    fn print_last_expression(code: &str) -> String {
+        // the expression follows the last top-level ';' or block
+        let chars: Vec<char> = code.chars().collect();
+        let mut expression_start = 0;
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if in_string {
+                match c {
+                    '\\' => i += 1,
+                    '"' => in_string = false,
+                    _ => (),
+                }
+            } else if let Some(end) = Rust_original::raw_string_end(&chars, i) {
+                i = end;
+            } else {
+                match c {
+                    '"' => in_string = true,
+                    '/' if chars.get(i + 1) == Some(&'/') => {
+                        while i < chars.len() && chars[i] != '\n' {
+                            i += 1;
+                        }
+                    }
+                    // block comments, that nest in rust
+                    '/' if chars.get(i + 1) == Some(&'*') => {
+                        let mut comments = 0;
+                        while i + 1 < chars.len() {
+                            match (chars[i], chars[i + 1]) {
+                                ('/', '*') => {
+                                    comments += 1;
+                                    i += 1;
+                                }
+                                ('*', '/') => {
+                                    comments -= 1;
+                                    i += 1;
+                                    if comments == 0 {
+                                        break;
+                                    }
+                                }
+                                _ => (),
+                            }
+                            i += 1;
+                        }
+                    }
+                    // skip char literals, that may be brackets
+                    '\'' if chars.get(i + 2) == Some(&'\'') => i += 2,
+                    '\'' if chars.get(i + 1) == Some(&'\\') => {
+                        while i + 1 < chars.len() && chars[i + 1] != '\'' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' => depth -= 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            expression_start = i + 1;
+                        }
+                    }
+                    ';' if depth == 0 => expression_start = i + 1,
+                    _ => (),
+                }
+            }
+            i += 1;
+        }
+        let expression: String = chars[expression_start.min(chars.len())..]
+            .iter()
+            .collect::<String>()
+            .lines()
+            .filter(|l| !l.trim_start().starts_with("//"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let first_word = expression.split_whitespace().next().unwrap_or("");
+        let items = [
+            "let",
+            "fn",
+            "use",
+            "struct",
+            "enum",
+            "impl",
+            "mod",
+            "const",
+            "static",
+            "return",
+            "break",
+            "continue",
+            "type",
+            "trait",
+            "pub",
+            "macro_rules!",
+        ];
+        if expression.trim().is_empty() || depth != 0 || items.contains(&first_word) {
+            return code.to_string();
+        }
+        let before: String = chars[..expression_start].iter().collect();
+        before
+            + "\nlet sniprun142859_last = {\n"
+            + expression.trim()
+            + "\n};\nif format!(\"{:?}\", sniprun142859_last) != \"()\" {\n    println!(\"{:?}\", sniprun142859_last);\n}\n"
+    }
+
+    /// the index of the last character of the raw string (`r"..."`, `br#"..."#`) at `i`, if any
+    fn raw_string_end(chars: &[char], i: usize) -> Option<usize> {
+        let ident = |j: usize| chars[j].is_alphanumeric() || chars[j] == '_';
+        let start = if i > 0 && chars[i - 1] == 'b' {
+            i - 1
+        } else {
+            i
+        };
+        if chars[i] != 'r' || (start > 0 && ident(start - 1)) {
+            return None;
+        }
+        let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+        if chars.get(i + 1 + hashes) != Some(&'"') {
+            return None;
+        }
+        let closing: Vec<char> = format!("\"{}", "#".repeat(hashes)).chars().collect();
+        (i + 2 + hashes..=chars.len() - closing.len())
+            .find(|&j| chars[j..j + closing.len()] == closing[..])
+            .map(|j| j + closing.len() - 1)
+    }
+
+    /// split a rust file in its top-level items, by following the nesting of braces
+    fn top_level_items(contents: &str) -> Vec<RustItem> {
+        let mut items = vec![];
+        let mut current: Option<(usize, String)> = None;
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut in_comment = false;
+        for (i, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if current.is_none() {
+                // comments and blank lines between items, doc comments belong to the next item
+                if trimmed.is_empty() || (trimmed.starts_with("//") && !trimmed.starts_with("///"))
+                {
+                    continue;
+                }
+                current = Some((i, String::new()));
+            }
+            let (start, mut code) = current.take().unwrap();
+            if !code.is_empty() {
+                code.push('\n');
+            }
+            code.push_str(line);
+
+            let mut last = ' ';
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_comment {
+                    if c == '*' && chars.peek() == Some(&'/') {
+                        chars.next();
+                        in_comment = false;
+                    }
+                    continue;
+                }
+                if in_string {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => in_string = false,
+                        _ => (),
+                    }
+                    continue;
+                }
+                match c {
+                    '/' if chars.peek() == Some(&'/') => break,
+                    '/' if chars.peek() == Some(&'*') => in_comment = true,
+                    '"' => in_string = true,
+                    // a char literal, not a lifetime
+                    '\'' => {
+                        let literal: String = chars.clone().take(3).collect();
+                        if literal.starts_with('\\') {
+                            chars.nth(2);
+                        } else if literal.chars().nth(1) == Some('\'') {
+                            chars.nth(1);
+                        }
+                    }
+                    '{' | '(' | '[' => depth += 1,
+                    '}' | ')' | ']' => depth -= 1,
+                    _ => (),
+                }
+                if !c.is_whitespace() {
+                    last = c;
+                }
+            }
+
+            let is_attribute = trimmed.starts_with("#") || trimmed.starts_with("//");
+            if depth <= 0
+                && !in_string
+                && !in_comment
+                && !is_attribute
+                && (last == '}' || last == ';')
+            {
+                depth = 0;
+                items.push(Rust_original::item(start, i + 1, code));
+            } else {
+                current = Some((start, code));
+            }
+        }
+        if let Some((start, code)) = current {
+            let end = start + code.lines().count();
+            items.push(Rust_original::item(start, end, code));
+        }
+        items
+    }
+
+    fn item(start: usize, end: usize, code: String) -> RustItem {
+        let head = code
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.starts_with('#') && !l.starts_with("//"))
+            .unwrap_or("")
+            .to_string();
+        let mut words: Vec<&str> = head
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .filter(|w| !w.is_empty())
+            .collect();
+        // visibility and qualifiers
+        while let Some(first) = words.first() {
+            let qualifier = first.starts_with("pub")
+                || ["crate", "async", "unsafe", "extern", "default"].contains(first)
+                || first.starts_with('"')
+                || (*first == "const" && words.get(1) == Some(&"fn"));
+            if !qualifier {
+                break;
+            }
+            words.remove(0);
+        }
+        let keyword = words.first().copied().unwrap_or("");
+        if keyword == "static" && words.get(1) == Some(&"mut") {
+            words.remove(1);
+        }
+        let name = words
+            .get(1)
+            .map(|w| {
+                w.chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+
+        let mut defines = vec![];
+        let mut impl_for = None;
+        match keyword {
+            "use" => {
+                // the last segment of each imported path, or its alias
+                let tree = head.trim_end_matches(';');
+                let tree = &tree[tree.find("use").unwrap_or(0) + 3..];
+                for path in tree.split([',', '{', '}']) {
+                    let path = path.trim();
+                    let imported = match path.rsplit_once(" as ") {
+                        Some((_, alias)) => alias.trim(),
+                        None => path.rsplit("::").next().unwrap_or(""),
+                    };
+                    if !imported.is_empty() && imported != "self" {
+                        defines.push(imported.to_string());
+                    }
+                }
+            }
+            "impl" => {
+                let mut rest = head[head.find("impl").unwrap_or(0) + 4..].trim_start();
+                // skip the generic parameters of the impl
+                if rest.starts_with('<') {
+                    let mut depth = 0;
+                    for (i, c) in rest.char_indices() {
+                        match c {
+                            '<' => depth += 1,
+                            '>' => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 {
+                            rest = &rest[i + 1..];
+                            break;
+                        }
+                    }
+                }
+                let target = match rest.rsplit_once(" for ") {
+                    Some((_, target)) => target,
+                    None => rest,
+                };
+                let target = target
+                    .trim_start()
+                    .trim_start_matches('&')
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+                    .next()
+                    .unwrap_or("");
+                impl_for = target.rsplit("::").next().map(String::from);
+            }
+            "macro_rules!" => defines.push(name),
+            "fn" | "struct" | "enum" | "union" | "trait" | "type" | "const" | "static" | "mod" => {
+                defines.push(name)
+            }
+            _ => (),
+        }
+        RustItem {
+            start,
+            end,
+            code,
+            defines,
+            impl_for,
+        }
+    }
+
+    fn identifiers(code: &str) -> std::collections::HashSet<String> {
+        code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|w| {
+                w.chars()
+                    .next()
+                    .is_some_and(|c| c.is_alphabetic() || c == '_')
+            })
+            .map(String::from)
+            .collect()
+    }
+}
+
 impl Interpreter for Rust_original {
     fn new_with_level(data: DataHolder, support_level: SupportLevel) -> Box<Rust_original> {
         //create a subfolder in the cache folder
@@ -28,6 +665,8 @@ impl Interpreter for Rust_original {
             data,
             support_level,
             code: String::from(""),
+            items: String::from(""),
+            glob_imports: String::from(""),
             rust_work_dir: rwd,
             bin_path: bp,
             main_file_path: mfp,
@@ -47,6 +686,36 @@ impl Interpreter for Rust_original {
         String::from("Rust_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![
+            Requirement::required("rustc", &["--version"]),
+            Requirement::optional("cargo", &["--version"], "to use the crates of the project"),
+        ]
+    }
+
+    fn has_treesitter_capability() -> bool {
+        true
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
+    fn get_options() -> Vec<InterpreterOption> {
+        vec![
+            InterpreterOption::new(
+                "offline",
+                OptionKind::Bool,
+                "build with the crates already downloaded only, or vendored by the project",
+            ),
+            InterpreterOption::new(
+                "print_last_expression",
+                OptionKind::Bool,
+                "print the Debug form of the expression that ends the code, if it is not () (default: true)",
+            ),
+        ]
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -62,7 +731,7 @@ impl Interpreter for Rust_original {
     }
 
     fn get_max_support_level() -> SupportLevel {
-        SupportLevel::Bloc
+        SupportLevel::File
     }
 
     fn fetch_code(&mut self) -> Result<(), SniprunError> {
@@ -82,51 +751,38 @@ impl Interpreter for Rust_original {
         } else {
             self.code = String::from("");
         }
+        if let Err(e) = self.fetch_items() {
+            info!("could not read the items of the file: {}", e);
+        }
         Ok(())
     }
 
     fn add_boilerplate(&mut self) -> Result<(), SniprunError> {
-        self.code = String::from("fn main() {") + &self.code + "}";
+        if self.get_bool_option("print_last_expression", true) {
+            self.code = Rust_original::print_last_expression(&self.code);
+        }
+        self.code = self.items.clone() + "fn main() {" + &self.code + "}";
         Ok(())
     }
 
     fn build(&mut self) -> Result<(), SniprunError> {
-        //write code to file
-        let mut _file =
-            File::create(&self.main_file_path).expect("Failed to create file for rust-original");
-        write(&self.main_file_path, &self.code).expect("Unable to write to file for rust-original");
-
-        //compile it (to the bin_path that arleady points to the rigth path)
-        let output = Command::new("rustc")
-            .arg("-O")
-            .arg("--out-dir")
-            .arg(&self.rust_work_dir)
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
-
-        //TODO if relevant, return the error number (parse it from stderr)
-        if !output.status.success() {
-            let error_message = String::from_utf8(output.stderr).unwrap();
-            //
-            //take first line and remove first 'error' word (redondant)
-            let first_line = error_message
-                .lines()
-                .next()
-                .unwrap_or_default()
-                .trim_start_matches("error: ")
-                .trim_start_matches("error");
-            return Err(SniprunError::CompilationError(first_line.to_owned()));
-        } else {
-            return Ok(());
+        match self.compile() {
+            // the names nothing else resolves may come from a glob import of the file
+            Err(SniprunError::CompilationError(e))
+                if !self.glob_imports.is_empty()
+                    && (e.contains("cannot find") || e.contains("failed to resolve")) =>
+            {
+                info!("adding the glob imports of the file: {}", e);
+                self.code = std::mem::take(&mut self.glob_imports) + &self.code;
+                self.compile()
+            }
+            result => result,
         }
     }
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
         if output.status.success() {
             return Ok(String::from_utf8(output.stdout).unwrap());
         } else {
@@ -148,6 +804,11 @@ mod test_rust_original {
         //because of file access & shared things
         simple_print();
         runtime_error();
+        get_items();
+        glob_imports();
+        project_crates();
+        workspace_crates();
+        print_last_expression();
     }
     fn simple_print() {
         let mut data = DataHolder::new();
@@ -183,4 +844,217 @@ mod test_rust_original {
             }
         }
     }
+
+    fn get_items() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("    println!(\"{}\", Square(3).area());");
+        data.range = [15, 15];
+
+        // outside of sniprun's own cargo project
+        data.filepath = std::env::temp_dir()
+            .join("sniprun_items.rs")
+            .to_string_lossy()
+            .to_string();
+        let dfpc = data.filepath.clone();
+        let mut file = File::create(&data.filepath).unwrap();
+        file.write_all(
+            b"use std::fmt::Write;\n\
+            use std::ops::Mul;\n\
+            \n\
+            /// a square\n\
+            #[derive(Clone)]\n\
+            struct Square(u32);\n\
+            \n\
+            impl Square {\n    fn area(&self) -> u32 {\n        mul(self.0, self.0) * SIDES / 4\n    }\n}\n\
+            const SIDES: u32 = 4;\n\
+            fn main() {\n\
+                println!(\"{}\", Square(3).area());\n\
+            }\n\
+            fn mul<T: Mul<Output = T> + Copy>(a: T, b: T) -> T {\n    a * b\n}\n",
+        )
+        .unwrap();
+
+        let mut interpreter = Rust_original::new(data);
+        let res = interpreter.run_at_level(SupportLevel::File);
+
+        // should panic if not an Ok()
+        let string_result = res.unwrap();
+        assert_eq!(string_result, "9\n");
+        assert!(!interpreter.code.contains("fmt::Write"));
+
+        std::fs::remove_file(dfpc).unwrap();
+    }
+
+    fn glob_imports() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from(
+            "    let m: HashMap<u8, u8> = [(1, 2)].into();\n    println!(\"{}\", m[&1]);",
+        );
+        data.range = [5, 6];
+        data.filepath = std::env::temp_dir()
+            .join("sniprun_globs.rs")
+            .to_string_lossy()
+            .to_string();
+        let dfpc = data.filepath.clone();
+        write(
+            &data.filepath,
+            "use super::*;\n\
+            use std::collections::*;\n\
+            \n\
+            fn main() {\n\
+                let m: HashMap<u8, u8> = [(1, 2)].into();\n\
+                println!(\"{}\", m[&1]);\n\
+            }\n",
+        )
+        .unwrap();
+
+        // the crate's own modules are left out, the external glob only added as it is needed
+        let mut interpreter = Rust_original::new(data);
+        let res = interpreter.run_at_level(SupportLevel::File);
+        assert_eq!(res.unwrap(), "2\n");
+        assert!(!interpreter.code.contains("super"));
+        assert!(interpreter.code.contains("std::collections::*"));
+
+        std::fs::remove_file(dfpc).unwrap();
+    }
+
+    fn project_crates() {
+        let root = std::env::temp_dir().join("sniprun_cargo_test");
+        let _ = std::fs::remove_dir_all(&root);
+        DirBuilder::new()
+            .recursive(true)
+            .create(root.join("mylib/src"))
+            .unwrap();
+        DirBuilder::new().create(root.join("src")).unwrap();
+        write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        write(
+            root.join("mylib/Cargo.toml"),
+            "[package]\nname = \"mylib\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        write(
+            root.join("mylib/src/lib.rs"),
+            "pub fn double(x: u32) -> u32 { 2 * x }",
+        )
+        .unwrap();
+        write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nedition = \"2018\"\n\n[dependencies]\nmylib = { path = \"mylib\" }\n",
+        )
+        .unwrap();
+
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("println!(\"{}\", mylib::double(21));");
+        data.filepath = root.join("src/main.rs").to_string_lossy().to_string();
+        data.interpreter_options = Some(neovim_lib::Value::Map(vec![(
+            neovim_lib::Value::from("Rust_original"),
+            neovim_lib::Value::Map(vec![(
+                neovim_lib::Value::from("offline"),
+                neovim_lib::Value::from(true),
+            )]),
+        )]));
+        let mut interpreter = Rust_original::new(data);
+        let res = interpreter.run_at_level(SupportLevel::Import);
+
+        // should panic if not an Ok()
+        assert_eq!(res.unwrap(), "42\n");
+        assert!(interpreter.bin_path.contains("cargo_project"));
+    }
+
+    fn workspace_crates() {
+        let root = std::env::temp_dir().join("sniprun_cargo_workspace_test");
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in ["app/src", "mylib/src"].iter() {
+            DirBuilder::new()
+                .recursive(true)
+                .create(root.join(dir))
+                .unwrap();
+        }
+        write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\", \"mylib\"]\n\n[workspace.package]\nedition = \"2021\"\n\n\
+            [workspace.dependencies]\nmylib = { path = \"mylib\" }\n",
+        )
+        .unwrap();
+        write(
+            root.join("mylib/Cargo.toml"),
+            "[package]\nname = \"mylib\"\nversion = \"0.1.0\"\nedition.workspace = true\n\n\
+            [features]\nfast = []\n",
+        )
+        .unwrap();
+        write(
+            root.join("mylib/src/lib.rs"),
+            "pub fn double(x: u32) -> u32 { 2 * x }",
+        )
+        .unwrap();
+        write(root.join("app/src/main.rs"), "fn main() {}").unwrap();
+        write(
+            root.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\nedition.workspace = true\n\n\
+            [dependencies.mylib]\nworkspace = true\nfeatures = [\n    \"fast\",\n]\n",
+        )
+        .unwrap();
+
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("println!(\"{}\", mylib::double(21));");
+        data.filepath = root.join("app/src/main.rs").to_string_lossy().to_string();
+        data.interpreter_options = Some(neovim_lib::Value::Map(vec![(
+            neovim_lib::Value::from("Rust_original"),
+            neovim_lib::Value::Map(vec![(
+                neovim_lib::Value::from("offline"),
+                neovim_lib::Value::from(true),
+            )]),
+        )]));
+        let mut interpreter = Rust_original::new_with_level(data.clone(), SupportLevel::Import);
+        let (_, manifest) = interpreter.project_dependencies().unwrap();
+        let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+        assert_eq!(manifest["package"]["edition"].as_str(), Some("2021"));
+        let mylib = manifest["dependencies"]["mylib"].as_table().unwrap();
+        assert_eq!(
+            mylib["path"].as_str(),
+            Some(root.join("mylib").to_str().unwrap())
+        );
+        assert_eq!(mylib["features"].as_array().unwrap().len(), 1);
+        assert!(mylib.get("workspace").is_none());
+        assert_eq!(
+            interpreter.run_at_level(SupportLevel::Import).unwrap(),
+            "42\n"
+        );
+
+        // a dependency cargo can't get doesn't matter to code that doesn't use it
+        write(
+            root.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nmissing = { path = \"../missing\" }\n",
+        )
+        .unwrap();
+        data.current_bloc = String::from("println!(\"{}\", 6 * 7);");
+        let mut interpreter = Rust_original::new_with_level(data, SupportLevel::Import);
+        let (_, manifest) = interpreter.project_dependencies().unwrap();
+        let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+        assert_eq!(manifest["package"]["edition"].as_str(), Some("2015"));
+        assert_eq!(
+            interpreter.run_at_level(SupportLevel::Import).unwrap(),
+            "42\n"
+        );
+    }
+
+    fn print_last_expression() {
+        let mut data = DataHolder::new();
+        data.current_bloc = String::from("let v = vec!['}', ';'];\n(v.len() * 21, v) // answer");
+        let mut interpreter = Rust_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "(42, ['}', ';'])\n");
+
+        // raw strings and block comments may hold quotes, brackets and ';'
+        data.current_bloc = String::from(
+            "let s = r#\"a \"quoted\" }; string\"#;\n/* a /* nested */ } comment; */\ns.len()",
+        );
+        let mut interpreter = Rust_original::new(data.clone());
+        assert_eq!(interpreter.run().unwrap(), "20\n");
+
+        // statements and unit values are not printed
+        data.current_bloc =
+            String::from("let a = 1;\nif a == 1 { println!(\"one\") }\nprintln!(\"two\")");
+        let mut interpreter = Rust_original::new(data);
+        assert_eq!(interpreter.run().unwrap(), "one\ntwo\n");
+    }
 }
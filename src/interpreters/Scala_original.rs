@@ -50,6 +50,17 @@ impl Interpreter for Scala_original {
         String::from("Scala_original")
     }
 
+    fn get_requirements() -> Vec<Requirement> {
+        vec![
+            Requirement::required("scalac", &["-version"]),
+            Requirement::required("scala", &["-version"]),
+        ]
+    }
+
+    fn has_streaming_capability() -> bool {
+        true
+    }
+
     fn default_for_filetype() -> bool {
         true
     }
@@ -110,12 +121,12 @@ impl Interpreter for Scala_original {
             .expect("Unable to write to file for language_subname");
 
         //compile it (to the bin_path that arleady points to the rigth path)
-        let output = Command::new("scalac")
-            .arg("-d")
-            .arg(&self.language_work_dir)
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("scalac")
+                .arg("-d")
+                .arg(&self.language_work_dir)
+                .arg(&self.main_file_path),
+        )?;
 
         // if relevant, return the error number (parse it from stderr)
         if !output.status.success() {
@@ -130,11 +141,12 @@ impl Interpreter for Scala_original {
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new("scala")
-            .arg("Main")
-            .current_dir(&self.language_work_dir)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("scala")
+                .arg("-classpath")
+                .arg(&self.language_work_dir)
+                .arg("Main"),
+        )?;
 
         if output.status.success() {
             //return stdout
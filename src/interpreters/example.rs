@@ -76,6 +76,21 @@ impl Interpreter for Language_subname {
         SupportLevel::Bloc
     }
 
+    fn get_options() -> Vec<InterpreterOption> {
+        // declare the options users can set in interpreter_options = { Language_subname = { ... } }
+        // and read them with the typed getters: self.get_string_option("compiler", "gcc")
+        vec![InterpreterOption::new(
+            "compiler",
+            OptionKind::String,
+            "compiler used to build the code",
+        )]
+    }
+
+    fn get_requirements() -> Vec<Requirement> {
+        // the executables this interpreter runs, and the arguments that print their version
+        vec![Requirement::required("compiler", &["--version"])]
+    }
+
     fn fetch_code(&mut self) -> Result<(), SniprunError> {
         //note: you probably don't have to modify, or even understand this function
 
@@ -125,13 +140,13 @@ impl Interpreter for Language_subname {
             .expect("Unable to write to file for language_subname");
 
         //compile it (to the bin_path that arleady points to the rigth path)
-        let output = Command::new("compiler")
-            .arg("--optimize") // for short snippets, that may contain a long loop
-            .arg("--out-dir")
-            .arg(&self.language_work_dir)
-            .arg(&self.main_file_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(
+            Command::new("compiler")
+                .arg("--optimize") // for short snippets, that may contain a long loop
+                .arg("--out-dir")
+                .arg(&self.language_work_dir)
+                .arg(&self.main_file_path),
+        )?;
 
         // if relevant, return the error number (parse it from stderr)
         if !output.status.success() {
@@ -145,9 +160,7 @@ impl Interpreter for Language_subname {
 
     fn execute(&mut self) -> Result<String, SniprunError> {
         //run th binary and get the std output (or stderr)
-        let output = Command::new(&self.bin_path)
-            .output()
-            .expect("Unable to start process");
+        let output = self.run_command(&mut Command::new(&self.bin_path))?;
 
         if output.status.success() {
             //return stdout
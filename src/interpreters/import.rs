@@ -1,5 +1,8 @@
 use crate::error::SniprunError;
-use crate::interpreter::{Interpreter, InterpreterUtils, ReplLikeInterpreter, SupportLevel};
+use crate::interpreter::{
+    Interpreter, InterpreterOption, InterpreterUtils, OptionKind, ReplLikeInterpreter, SupportLevel,
+};
+use crate::requirement::Requirement;
 use crate::DataHolder;
 use log::info;
 use serde_json::Value;
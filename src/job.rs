@@ -0,0 +1,725 @@
+//! Bookkeeping of the external processes (compilers, interpreters...) spawned
+//! while running a snippet, so that a run can be stopped from the main loop,
+//! so that no process outlives its timeout, and so that the output of long
+//! programs can be streamed to the terminal display
+
+use crate::environment::Environment;
+use crate::error::SniprunError;
+use crate::interpreter::SupportLevel;
+use crate::stdin::StdinSource;
+use crate::DataHolder;
+use log::info;
+use neovim_lib::{Neovim, NeovimApi, Value};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+///The step of the run the spawned processes belong to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Build,
+    Execute,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Phase::Build => f.write_str("build"),
+            Phase::Execute => f.write_str("execute"),
+        }
+    }
+}
+
+///A single snippet run (from :SnipRun until the result is displayed)
+#[derive(Debug)]
+pub struct Job {
+    ///process group ids of the children currently running for this job
+    pgids: Vec<u32>,
+    ///whether the user asked to stop this run
+    cancelled: bool,
+    ///step of the run currently happening
+    phase: Phase,
+    ///whether some output was streamed to the terminal display
+    streamed: bool,
+
+    ///what has been recorded so far about the run
+    record: RunRecord,
+}
+
+///What is known about a run, apart from the interpreter's own result
+#[derive(Clone, Debug)]
+struct RunRecord {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    exit_status: Option<i32>,
+    build_time: Duration,
+    run_time: Duration,
+    interpreter: String,
+    support_level: SupportLevel,
+    artifacts: Vec<String>,
+    attempts: Vec<String>,
+    all_attempts_failed: bool,
+}
+
+impl Default for RunRecord {
+    fn default() -> Self {
+        RunRecord {
+            stdout: vec![],
+            stderr: vec![],
+            exit_status: None,
+            build_time: Duration::default(),
+            run_time: Duration::default(),
+            interpreter: String::new(),
+            support_level: SupportLevel::Unsupported,
+            artifacts: vec![],
+            attempts: vec![],
+            all_attempts_failed: false,
+        }
+    }
+}
+
+///Everything known about a finished run, display functions pick what they want to show
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunResult {
+    ///what the interpreter made of the run: the output to show, or the error
+    pub result: Result<String, SniprunError>,
+    ///everything written to the standard output by the processes of the run
+    pub stdout: String,
+    ///everything written to the standard error, including compiler warnings
+    pub stderr: String,
+    ///exit code of the last process that ran, None if it was killed or nothing ran
+    pub exit_status: Option<i32>,
+    ///time spent in the processes of the build phase
+    pub build_time: Duration,
+    ///time spent in the processes of the execute phase
+    pub run_time: Duration,
+    ///name of the interpreter that (last) ran the code
+    pub interpreter: String,
+    pub support_level: SupportLevel,
+    ///files produced by the run (binaries, plots...)
+    pub artifacts: Vec<String>,
+    ///the interpreters and levels that could not run the code before this one
+    pub attempts: Vec<String>,
+    ///no interpreter could run the code, the error already lists the attempts
+    pub all_attempts_failed: bool,
+}
+
+impl RunResult {
+    ///a result that did not come from a run, eg: an invalid configuration
+    pub fn from_result(result: Result<String, SniprunError>) -> Self {
+        Job {
+            pgids: vec![],
+            cancelled: false,
+            phase: Phase::Execute,
+            streamed: false,
+            record: RunRecord::default(),
+        }
+        .run_result(result)
+    }
+
+    ///The interpreter's result, completed with what it left out of it:
+    ///stdout printed before a crash, the full stderr, or warnings of a successful run
+    pub fn detailed(&self) -> Result<String, SniprunError> {
+        let stdout = self.stdout.trim_end_matches('\n');
+        let stderr = self.stderr.trim_end_matches('\n');
+        // the interpreters that were tried before the one that ran the code
+        let attempts = if self.attempts.is_empty() || self.all_attempts_failed {
+            String::new()
+        } else {
+            format!(
+                "Ran with {} at level {}, after:\n{}",
+                self.interpreter,
+                self.support_level,
+                self.attempts.join("\n")
+            )
+        };
+        match &self.result {
+            Ok(message) => {
+                let mut context = vec![message.trim_end_matches('\n')];
+                if !stderr.is_empty() && !message.contains(stderr) {
+                    context.push(stderr);
+                }
+                if !attempts.is_empty() {
+                    context.push(&attempts);
+                }
+                if context.len() == 1 {
+                    Ok(message.clone())
+                } else {
+                    Ok(context.join("\n"))
+                }
+            }
+            Err(e) => {
+                let message = e.to_string();
+                let mut context = vec![];
+                if !stdout.is_empty() && !message.contains(stdout) {
+                    context.push(stdout);
+                }
+                if !stderr.is_empty() && !message.contains(stderr) {
+                    context.push(stderr);
+                }
+                if !attempts.is_empty() {
+                    context.push(&attempts);
+                }
+                if context.is_empty() {
+                    Err(e.clone())
+                } else {
+                    context.push(&message);
+                    Err(SniprunError::CustomError(context.join("\n")))
+                }
+            }
+        }
+    }
+}
+
+impl Job {
+    pub fn new() -> Arc<Mutex<Job>> {
+        Arc::new(Mutex::new(Job {
+            pgids: vec![],
+            cancelled: false,
+            phase: Phase::Execute,
+            streamed: false,
+            record: RunRecord::default(),
+        }))
+    }
+
+    ///the result of the run, completed with what was recorded during the run
+    pub fn run_result(&self, result: Result<String, SniprunError>) -> RunResult {
+        let record = self.record.clone();
+        RunResult {
+            result,
+            stdout: String::from_utf8_lossy(&record.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&record.stderr).to_string(),
+            exit_status: record.exit_status,
+            build_time: record.build_time,
+            run_time: record.run_time,
+            interpreter: record.interpreter,
+            support_level: record.support_level,
+            artifacts: record.artifacts,
+            attempts: record.attempts,
+            all_attempts_failed: record.all_attempts_failed,
+        }
+    }
+
+    ///kill every process (and its children) spawned by this job,
+    ///and prevent any other process from being started by it
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+        for pgid in self.pgids.drain(..) {
+            kill_tree(pgid);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    pub fn has_streamed(&self) -> bool {
+        self.streamed
+    }
+}
+
+///How the processes spawned by an interpreter should be run
+#[derive(Clone, Debug, Default)]
+pub struct ProcessSettings {
+    ///kill the process (and its children) if it runs for longer than that
+    pub timeout: Option<Duration>,
+    ///forward the output of the execute phase to the terminal display as it arrives
+    pub stream: bool,
+    ///working directory and extra variables
+    pub env: Environment,
+}
+
+///record which step of the run is happening, processes spawned from now on belong to it
+pub fn set_phase(data: &DataHolder, phase: Phase) {
+    if let Some(job) = &data.job {
+        job.lock().unwrap().phase = phase;
+    }
+}
+
+///record which interpreter is running the code, and at which level
+pub fn set_interpreter(data: &DataHolder, name: &str, level: SupportLevel) {
+    if let Some(job) = &data.job {
+        let mut job = job.lock().unwrap();
+        job.record.interpreter = name.to_string();
+        job.record.support_level = level;
+    }
+}
+
+///forget what the previous attempt to run the code printed and produced
+pub fn new_attempt(data: &DataHolder) {
+    let streamed = match &data.job {
+        Some(job) => {
+            let mut job = job.lock().unwrap();
+            let attempts = std::mem::take(&mut job.record.attempts);
+            job.record = RunRecord {
+                attempts,
+                ..RunRecord::default()
+            };
+            std::mem::take(&mut job.streamed)
+        }
+        None => false,
+    };
+    // what the previous attempt streamed stays in the terminal, closed as failed,
+    // and the output of the next one goes to a new section
+    if let (true, Some(nvim)) = (streamed, &data.nvim_instance) {
+        let res = nvim.lock().unwrap().command(
+            "lua require\"sniprun.display\".term_stream_end(\"Could not run the code this way, trying another\", false)",
+        );
+        info!(
+            "[JOB] end streaming output of the failed attempt: {:?}",
+            res
+        );
+    }
+}
+
+///record an attempt to run the code that failed because of the interpreter
+pub fn add_attempt(data: &DataHolder, description: &str) {
+    if let Some(job) = &data.job {
+        job.lock()
+            .unwrap()
+            .record
+            .attempts
+            .push(description.to_string());
+    }
+}
+
+///record that every attempt failed, and no interpreter ran the code
+pub fn all_attempts_failed(data: &DataHolder) {
+    if let Some(job) = &data.job {
+        job.lock().unwrap().record.all_attempts_failed = true;
+    }
+}
+
+///record a file produced by the run
+pub fn add_artifact(data: &DataHolder, path: &str) {
+    if let Some(job) = &data.job {
+        job.lock().unwrap().record.artifacts.push(path.to_string());
+    }
+}
+
+///kill a whole process group
+pub fn kill_tree(pgid: u32) {
+    let res = Command::new("kill")
+        .arg("-s")
+        .arg("KILL")
+        .arg("--")
+        .arg(format!("-{}", pgid))
+        .output();
+    info!("[JOB] killed process group {}: {:?}", pgid, res);
+}
+
+///write a line of output to the terminal display
+pub fn stream_line(nvim: &Arc<Mutex<Neovim>>, line: &str) {
+    let res = nvim.lock().unwrap().execute_lua(
+        "require\"sniprun.display\".term_stream_write(...)",
+        vec![Value::from(line.trim_end_matches('\n'))],
+    );
+    if res.is_err() {
+        info!("[JOB] could not stream output: {:?}", res);
+    }
+}
+
+/// Wait for the child to exit while forwarding every line it outputs (to the terminal display)
+fn wait_with_streamed_output<F: FnMut(&str)>(
+    mut child: Child,
+    mut forward: F,
+) -> std::io::Result<Output> {
+    fn forward_lines<R: Read + Send + 'static>(
+        pipe: Option<R>,
+        is_stdout: bool,
+        sender: mpsc::Sender<(bool, Vec<u8>)>,
+    ) -> Option<thread::JoinHandle<()>> {
+        let mut reader = BufReader::new(pipe?);
+        Some(thread::spawn(move || loop {
+            let mut line = vec![];
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if sender.send((is_stdout, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }))
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let readers = vec![
+        forward_lines(child.stdout.take(), true, sender.clone()),
+        forward_lines(child.stderr.take(), false, sender),
+    ];
+
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    for (is_stdout, line) in receiver {
+        forward(&String::from_utf8_lossy(&line));
+        if is_stdout {
+            stdout.extend(line);
+        } else {
+            stderr.extend(line);
+        }
+    }
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+
+    let status = child.wait()?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Run a command to completion and collect its output, like `Command::output()` does.
+/// The child gets its own process group, registered in the current job (if any),
+/// so that it and all its children can be killed when the user stops the run,
+/// or when it runs for longer than the timeout.
+/// Without a given stdin, the user code gets the one configured for the run (or /dev/null),
+/// and the build tools /dev/null.
+pub fn run_command(
+    data: &DataHolder,
+    command: &mut Command,
+    stdin: Option<Stdio>,
+    settings: &ProcessSettings,
+) -> Result<Output, SniprunError> {
+    let mut phase = Phase::Execute;
+    if let Some(job) = &data.job {
+        let job = job.lock().unwrap();
+        if job.is_cancelled() {
+            return Err(SniprunError::Cancelled);
+        }
+        phase = job.phase;
+    }
+
+    let mut input = None;
+    let stdin = match stdin {
+        Some(stdin) => stdin,
+        None if phase == Phase::Execute && !data.stdin.is_empty() => {
+            input = Some(data.stdin.parse::<StdinSource>()?.read(data)?);
+            Stdio::piped()
+        }
+        None => Stdio::null(),
+    };
+    // the same directory for the build tools and the code
+    if let Some(cwd) = &settings.env.cwd {
+        command.current_dir(cwd);
+    }
+    command.envs(settings.env.vars.iter().map(|(k, v)| (k, v)));
+    command
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    let mut child = command.spawn().map_err(|e| {
+        SniprunError::InternalError(format!(
+            "Unable to start process {:?}: {}",
+            command.get_program(),
+            e
+        ))
+    })?;
+
+    // written from another thread, in case the process does not read it all before writing its output
+    if let (Some(input), Some(mut child_stdin)) = (input, child.stdin.take()) {
+        thread::spawn(move || {
+            let _ = child_stdin.write_all(&input);
+        });
+    }
+
+    let pgid = child.id();
+    supervise(data, pgid, settings, |stream_to| match stream_to {
+        Some(nvim) => wait_with_streamed_output(child, |line| stream_line(nvim, line)),
+        None => child.wait_with_output(),
+    })
+}
+
+/// Wait for a process group to do its part of the run: it is registered in the current job
+/// (if any) so that the user can stop it, and killed if it runs for longer than the timeout.
+/// `wait` returns what the processes output, forwarding it as it arrives to the terminal
+/// display if it is given the neovim instance.
+/// What they output and how long it took is recorded in the job.
+pub fn supervise<F>(
+    data: &DataHolder,
+    pgid: u32,
+    settings: &ProcessSettings,
+    wait: F,
+) -> Result<Output, SniprunError>
+where
+    F: FnOnce(Option<&Arc<Mutex<Neovim>>>) -> std::io::Result<Output>,
+{
+    let timeout = settings.timeout;
+    let mut phase = Phase::Execute;
+    let mut stream_to = None;
+    let mut first_stream = false;
+    if let Some(job) = &data.job {
+        let mut job = job.lock().unwrap();
+        if job.is_cancelled() {
+            //stop arrived before the process could be registered
+            kill_tree(pgid);
+            return Err(SniprunError::Cancelled);
+        }
+        job.pgids.push(pgid);
+        phase = job.phase;
+        // only the output of the user's code is worth streaming, not the compiler's
+        if settings.stream && phase == Phase::Execute {
+            stream_to = data.nvim_instance.clone();
+        }
+        if stream_to.is_some() && !job.streamed {
+            job.streamed = true;
+            first_stream = true;
+        }
+    }
+    if let (Some(nvim), true) = (&stream_to, first_stream) {
+        let res = nvim
+            .lock()
+            .unwrap()
+            .command("lua require\"sniprun.display\".term_stream_start()");
+        info!("[JOB] start streaming output: {:?}", res);
+    }
+
+    // the watchdog kills the process group if it is not done before the deadline
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let (done, done_receiver) = mpsc::channel::<()>();
+    let watchdog = timeout.map(|duration| {
+        let timed_out = timed_out.clone();
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = done_receiver.recv_timeout(duration) {
+                info!("[JOB] process {} timed out", pgid);
+                timed_out.store(true, Ordering::SeqCst);
+                kill_tree(pgid);
+            }
+        })
+    });
+
+    let start = Instant::now();
+    let output = wait(stream_to.as_ref());
+    let elapsed = start.elapsed();
+
+    let _ = done.send(());
+    if let Some(watchdog) = watchdog {
+        let _ = watchdog.join();
+    }
+
+    if let Some(job) = &data.job {
+        let mut job = job.lock().unwrap();
+        job.pgids.retain(|&p| p != pgid);
+        match phase {
+            Phase::Build => job.record.build_time += elapsed,
+            Phase::Execute => job.record.run_time += elapsed,
+        }
+        if let Ok(output) = &output {
+            job.record.stdout.extend(&output.stdout);
+            job.record.stderr.extend(&output.stderr);
+            job.record.exit_status = output.status.code();
+        }
+        if job.is_cancelled() {
+            info!("[JOB] process {} was stopped", pgid);
+            return Err(SniprunError::Cancelled);
+        }
+    }
+    if timed_out.load(Ordering::SeqCst) {
+        return Err(SniprunError::TimeoutError(
+            phase,
+            timeout.unwrap_or_default().as_secs(),
+        ));
+    }
+
+    output.map_err(|e| SniprunError::InternalError(format!("Failed to wait on process: {}", e)))
+}
+
+#[cfg(test)]
+mod test_job {
+    use super::*;
+
+    #[test]
+    fn stop_running_process() {
+        let mut data = DataHolder::new();
+        let job = Job::new();
+        data.job = Some(job.clone());
+
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            job.lock().unwrap().cancel();
+        });
+        let start = std::time::Instant::now();
+        let res = run_command(
+            &data,
+            Command::new("sh").arg("-c").arg("sleep 10; echo done"),
+            None,
+            &ProcessSettings::default(),
+        );
+        stopper.join().unwrap();
+
+        assert_eq!(res, Err(SniprunError::Cancelled));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // a stopped job does not start anything anymore
+        let res = run_command(
+            &data,
+            Command::new("echo").arg("hi"),
+            None,
+            &ProcessSettings::default(),
+        );
+        assert_eq!(res, Err(SniprunError::Cancelled));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut data = DataHolder::new();
+        data.job = Some(Job::new());
+        set_phase(&data, Phase::Build);
+        let settings = ProcessSettings {
+            timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+
+        let start = std::time::Instant::now();
+        let res = run_command(
+            &data,
+            Command::new("sh").arg("-c").arg("sleep 10; echo done"),
+            None,
+            &settings,
+        );
+        assert_eq!(res, Err(SniprunError::TimeoutError(Phase::Build, 1)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // fast enough
+        set_phase(&data, Phase::Execute);
+        let output = run_command(&data, Command::new("echo").arg("hi"), None, &settings).unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+    }
+
+    #[test]
+    fn record_run() {
+        let mut data = DataHolder::new();
+        let job = Job::new();
+        data.job = Some(job.clone());
+        set_interpreter(&data, "Bash_original", SupportLevel::Bloc);
+
+        let res = run_command(
+            &data,
+            Command::new("sh")
+                .arg("-c")
+                .arg("echo before crash; echo oops >&2; exit 3"),
+            None,
+            &ProcessSettings::default(),
+        );
+        assert!(res.is_ok());
+
+        let run = job
+            .lock()
+            .unwrap()
+            .run_result(Err(SniprunError::RuntimeError(String::from("oops"))));
+        assert_eq!(run.interpreter, "Bash_original");
+        assert_eq!(run.support_level, SupportLevel::Bloc);
+        assert_eq!(run.exit_status, Some(3));
+        assert_eq!(run.stdout, "before crash\n");
+        assert_eq!(
+            run.detailed(),
+            Err(SniprunError::CustomError(String::from(
+                "before crash\nRuntimeError: oops"
+            )))
+        );
+
+        let warning = job
+            .lock()
+            .unwrap()
+            .run_result(Ok(String::from("before crash\n")));
+        assert_eq!(warning.detailed(), Ok(String::from("before crash\noops")));
+    }
+
+    #[test]
+    fn feed_stdin() {
+        let mut data = DataHolder::new();
+        data.job = Some(Job::new());
+        data.stdin = String::from("text:hello\\nworld");
+
+        let res = run_command(
+            &data,
+            &mut Command::new("cat"),
+            None,
+            &ProcessSettings::default(),
+        );
+        assert_eq!(res.unwrap().stdout, b"hello\nworld\n".to_vec());
+
+        // compilers do not get the snippet's stdin
+        set_phase(&data, Phase::Build);
+        let res = run_command(
+            &data,
+            &mut Command::new("cat"),
+            None,
+            &ProcessSettings::default(),
+        );
+        assert_eq!(res.unwrap().stdout, b"".to_vec());
+    }
+
+    #[test]
+    fn environment() {
+        let data = DataHolder::new();
+        let settings = ProcessSettings {
+            env: Environment {
+                cwd: Some(std::path::PathBuf::from("/tmp")),
+                vars: vec![(String::from("SNIPRUN_TEST"), String::from("ok"))],
+            },
+            ..Default::default()
+        };
+        let res = run_command(
+            &data,
+            Command::new("sh").arg("-c").arg("echo $PWD $SNIPRUN_TEST"),
+            None,
+            &settings,
+        );
+        assert_eq!(res.unwrap().stdout, b"/tmp ok\n".to_vec());
+
+        // build tools run in the same directory
+        let mut data = data;
+        data.job = Some(Job::new());
+        set_phase(&data, Phase::Build);
+        let res = run_command(&data, &mut Command::new("pwd"), None, &settings);
+        assert_eq!(res.unwrap().stdout, b"/tmp\n".to_vec());
+    }
+
+    #[test]
+    fn stream_output() {
+        let mut data = DataHolder::new();
+        let job = Job::new();
+        data.job = Some(job.clone());
+
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg("echo one; echo two >&2; echo three")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut lines = vec![];
+        let output = wait_with_streamed_output(child, |line| lines.push(line.to_string())).unwrap();
+        lines.sort();
+        assert_eq!(lines, vec!["one\n", "three\n", "two\n"]);
+        assert_eq!(output.stdout, b"one\nthree\n".to_vec());
+        assert_eq!(output.stderr, b"two\n".to_vec());
+
+        // an attempt streamed its output before failing, the next one streams it again
+        job.lock().unwrap().streamed = true;
+        new_attempt(&data);
+        assert!(!job.lock().unwrap().has_streamed());
+    }
+
+    #[test]
+    fn run_without_job() {
+        let data = DataHolder::new();
+        let output = run_command(
+            &data,
+            Command::new("echo").arg("hi"),
+            None,
+            &ProcessSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+    }
+}
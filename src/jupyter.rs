@@ -0,0 +1,654 @@
+//! Client of the Jupyter messaging protocol, to run code on a kernel
+//! (https://jupyter-client.readthedocs.io/en/stable/messaging.html).
+//!
+//! Messages go over ZMTP 3.0 (the ZeroMQ wire protocol, without security mechanism) on the
+//! TCP ports of the kernel's connection file, and are signed with HMAC-SHA256 using its key.
+
+use crate::error::SniprunError;
+use crate::job::{self, ProcessSettings};
+use crate::repl::SessionKey;
+use crate::DataHolder;
+use hmac::{Hmac, KeyInit, Mac};
+use log::info;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Output};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+///separates the routing identities from the message in a multipart message
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+///version of the messaging protocol the messages follow
+const PROTOCOL_VERSION: &str = "5.3";
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+///Where a kernel listens, and the key to sign messages with, from its connection file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub ip: String,
+    pub shell_port: u16,
+    pub iopub_port: u16,
+    pub key: String,
+}
+
+impl ConnectionInfo {
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let value: Value = serde_json::from_str(content).map_err(|e| invalid(&e.to_string()))?;
+        let string = |key: &str| value[key].as_str().unwrap_or_default().to_string();
+        let port = |key: &str| {
+            value[key]
+                .as_u64()
+                .map(|p| p as u16)
+                .ok_or_else(|| invalid(&format!("no {} in the connection file", key)))
+        };
+        if !matches!(value["transport"].as_str(), None | Some("tcp")) {
+            return Err(invalid("only the tcp transport is supported"));
+        }
+        let scheme = string("signature_scheme");
+        if !string("key").is_empty() && !matches!(scheme.as_str(), "" | "hmac-sha256") {
+            return Err(invalid(&format!("unsupported signature scheme {}", scheme)));
+        }
+        Ok(ConnectionInfo {
+            ip: string("ip"),
+            shell_port: port("shell_port")?,
+            iopub_port: port("iopub_port")?,
+            key: string("key"),
+        })
+    }
+}
+
+///the 64 bytes each side sends first: ZMTP 3.0, NULL mechanism, as a client
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+///the READY command that ends the handshake, with the type of the sending socket
+fn ready(socket_type: &str) -> Vec<u8> {
+    let mut body = vec![5];
+    body.extend(b"READY");
+    body.push(11);
+    body.extend(b"Socket-Type");
+    body.extend(&(socket_type.len() as u32).to_be_bytes());
+    body.extend(socket_type.as_bytes());
+    body
+}
+
+fn write_frame(stream: &mut impl Write, body: &[u8], more: bool, command: bool) -> io::Result<()> {
+    let mut flags = if more { 0x01 } else { 0 } | if command { 0x04 } else { 0 };
+    let mut frame = vec![];
+    if body.len() > 255 {
+        flags |= 0x02;
+        frame.push(flags);
+        frame.extend(&(body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend(body);
+    stream.write_all(&frame)
+}
+
+///the body of the next frame, whether more frames of the message follow, and whether it is a
+///command rather than part of a message
+fn read_frame(stream: &mut impl Read) -> io::Result<(Vec<u8>, bool, bool)> {
+    let mut flags = [0u8; 1];
+    stream.read_exact(&mut flags)?;
+    let size = if flags[0] & 0x02 != 0 {
+        let mut size = [0u8; 8];
+        stream.read_exact(&mut size)?;
+        u64::from_be_bytes(size) as usize
+    } else {
+        let mut size = [0u8; 1];
+        stream.read_exact(&mut size)?;
+        size[0] as usize
+    };
+    let mut body = vec![0u8; size];
+    stream.read_exact(&mut body)?;
+    Ok((body, flags[0] & 0x01 != 0, flags[0] & 0x04 != 0))
+}
+
+///A ZMTP connection, whose incoming messages are read by a thread as they arrive
+struct Socket {
+    stream: TcpStream,
+    messages: mpsc::Receiver<Vec<Vec<u8>>>,
+}
+
+impl Socket {
+    fn connect(ip: &str, port: u16, socket_type: &str) -> io::Result<Self> {
+        let mut stream = TcpStream::connect((ip, port))?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.write_all(&greeting())?;
+        let mut peer = [0u8; 64];
+        stream.read_exact(&mut peer)?;
+        if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 {
+            return Err(invalid("the kernel does not speak ZMTP 3"));
+        }
+        write_frame(&mut stream, &ready(socket_type), false, true)?;
+        match read_frame(&mut stream)? {
+            (_, _, true) => (),
+            _ => return Err(invalid("the kernel did not send its READY command")),
+        }
+        stream.set_read_timeout(None)?;
+
+        let mut reader = stream.try_clone()?;
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut message = vec![];
+            while let Ok((body, more, command)) = read_frame(&mut reader) {
+                if command {
+                    continue;
+                }
+                message.push(body);
+                if !more && sender.send(std::mem::take(&mut message)).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Socket { stream, messages })
+    }
+
+    fn send(&mut self, frames: &[&[u8]]) -> io::Result<()> {
+        for (i, frame) in frames.iter().enumerate() {
+            write_frame(&mut self.stream, frame, i + 1 < frames.len(), false)?;
+        }
+        self.stream.flush()
+    }
+
+    ///the next message, waiting at most for the timeout if there is one
+    fn recv(&self, timeout: Option<Duration>) -> io::Result<Vec<Vec<u8>>> {
+        let closed = || io::Error::new(io::ErrorKind::ConnectionAborted, "the kernel is gone");
+        match timeout {
+            Some(timeout) => self.messages.recv_timeout(timeout).map_err(|e| match e {
+                mpsc::RecvTimeoutError::Timeout => {
+                    io::Error::new(io::ErrorKind::TimedOut, "the kernel did not answer")
+                }
+                mpsc::RecvTimeoutError::Disconnected => closed(),
+            }),
+            None => self.messages.recv().map_err(|_| closed()),
+        }
+    }
+}
+
+impl Drop for Socket {
+    ///ends the reader thread too, that would otherwise wait for messages forever
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+///A message from the kernel
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub msg_type: String,
+    ///id of the request this message is about
+    pub parent_id: String,
+    pub content: Value,
+}
+
+///What the code did on the kernel
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Execution {
+    ///printed output, and the values of the results and displays as text
+    pub stdout: String,
+    ///output to stderr, and the traceback of the error, without colors
+    pub stderr: String,
+    ///"<exception name>: <value>" if the code raised one
+    pub error: Option<String>,
+}
+
+///A connection to the shell and iopub channels of a running kernel
+pub struct KernelClient {
+    shell: Socket,
+    iopub: Socket,
+    key: Vec<u8>,
+    session: String,
+    sent: u32,
+}
+
+impl KernelClient {
+    pub fn connect(info: &ConnectionInfo) -> io::Result<Self> {
+        let shell = Socket::connect(&info.ip, info.shell_port, "DEALER")?;
+        let mut iopub = Socket::connect(&info.ip, info.iopub_port, "SUB")?;
+        // subscribe to every topic
+        iopub.send(&[&[1]])?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Ok(KernelClient {
+            shell,
+            iopub,
+            key: info.key.as_bytes().to_vec(),
+            session: format!("{:x}{:x}", std::process::id(), nanos),
+            sent: 0,
+        })
+    }
+
+    ///Connect to the kernel of that connection file once it is ready: the file is written,
+    ///the kernel answers a kernel_info_request, and its iopub messages arrive
+    pub fn wait_for(connection_file: &str, timeout: Duration) -> io::Result<Self> {
+        let deadline = Instant::now() + timeout;
+        let mut client: Option<KernelClient> = None;
+        loop {
+            let ready = match client.as_mut() {
+                Some(connected) => connected.kernel_info(),
+                None => std::fs::read_to_string(connection_file)
+                    .and_then(|content| ConnectionInfo::parse(&content))
+                    .and_then(|info| KernelClient::connect(&info))
+                    .and_then(|mut connected| {
+                        let ready = connected.kernel_info();
+                        client = Some(connected);
+                        ready
+                    }),
+            };
+            match ready {
+                Ok(()) => return Ok(client.unwrap()),
+                Err(e) if Instant::now() > deadline => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("the jupyter kernel is not ready: {}", e),
+                    ))
+                }
+                Err(e) => {
+                    info!("[JUPYTER] kernel not ready yet: {}", e);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    }
+
+    ///check that the kernel answers on the shell channel, and that its iopub messages arrive
+    fn kernel_info(&mut self) -> io::Result<()> {
+        let timeout = Some(Duration::from_secs(1));
+        let id = self.send("kernel_info_request", json!({}))?;
+        self.wait_for_reply(&self.shell, &id, "kernel_info_reply", timeout)?;
+        // the kernel publishes its status for every request, once the subscription is active
+        self.wait_for_reply(&self.iopub, &id, "status", timeout)?;
+        Ok(())
+    }
+
+    fn wait_for_reply(
+        &self,
+        socket: &Socket,
+        id: &str,
+        msg_type: &str,
+        timeout: Option<Duration>,
+    ) -> io::Result<Message> {
+        loop {
+            if let Some(message) = parse(&self.key, &socket.recv(timeout)?) {
+                if message.parent_id == id && message.msg_type == msg_type {
+                    return Ok(message);
+                }
+            }
+        }
+    }
+
+    ///Run the code, calling `on_stdout` with the output as it arrives
+    pub fn execute(
+        &mut self,
+        code: &str,
+        on_stdout: &mut dyn FnMut(&str),
+    ) -> io::Result<Execution> {
+        let content = json!({
+            "code": code,
+            "silent": false,
+            "store_history": true,
+            "user_expressions": {},
+            "allow_stdin": false,
+            "stop_on_error": true,
+        });
+        let id = self.send("execute_request", content)?;
+        let mut execution = Execution::default();
+        loop {
+            let message = match parse(&self.key, &self.iopub.recv(None)?) {
+                Some(message) if message.parent_id == id => message,
+                _ => continue,
+            };
+            let content = &message.content;
+            match message.msg_type.as_str() {
+                "stream" if content["name"] == "stderr" => {
+                    execution.stderr += content["text"].as_str().unwrap_or_default()
+                }
+                "stream" => {
+                    let text = content["text"].as_str().unwrap_or_default();
+                    on_stdout(text);
+                    execution.stdout += text;
+                }
+                "execute_result" | "display_data" => {
+                    if let Some(text) = content["data"]["text/plain"].as_str() {
+                        on_stdout(text);
+                        execution.stdout += text;
+                        execution.stdout += "\n";
+                    }
+                }
+                "error" => {
+                    let traceback: Vec<String> = content["traceback"]
+                        .as_array()
+                        .map(|lines| {
+                            lines
+                                .iter()
+                                .filter_map(|l| l.as_str())
+                                .map(strip_colors)
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    execution.stderr += &traceback.join("\n");
+                    execution.stderr += "\n";
+                    execution.error = Some(format!(
+                        "{}: {}",
+                        content["ename"].as_str().unwrap_or_default(),
+                        content["evalue"].as_str().unwrap_or_default()
+                    ));
+                }
+                "status" if content["execution_state"] == "idle" => break,
+                _ => (),
+            }
+        }
+        // an aborted request (the kernel is shutting down...) publishes no error
+        let reply = self.wait_for_reply(
+            &self.shell,
+            &id,
+            "execute_reply",
+            Some(Duration::from_secs(5)),
+        )?;
+        if execution.error.is_none() && reply.content["status"] != "ok" {
+            execution.error = Some(format!("execution {}", reply.content["status"]));
+        }
+        Ok(execution)
+    }
+
+    ///send a request on the shell channel, returns its id
+    fn send(&mut self, msg_type: &str, content: Value) -> io::Result<String> {
+        self.sent += 1;
+        let id = format!("{}_{}", self.session, self.sent);
+        let header = json!({
+            "msg_id": id,
+            "session": self.session,
+            "username": std::env::var("USER").unwrap_or_else(|_| String::from("sniprun")),
+            "date": iso8601(SystemTime::now()),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        })
+        .to_string();
+        let content = content.to_string();
+        let parts: [&[u8]; 4] = [header.as_bytes(), b"{}", b"{}", content.as_bytes()];
+        let signature = sign(&self.key, &parts);
+        let mut frames: Vec<&[u8]> = vec![DELIMITER, signature.as_bytes()];
+        frames.extend(parts.iter());
+        self.shell.send(&frames)?;
+        Ok(id)
+    }
+}
+
+///how long a new kernel may take to start before the first request
+const KERNEL_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+///Run the code on the kernel of that name (as `jupyter-kernel --kernel=<name>` knows it), kept
+///alive between runs in the REPL session of the interpreter; its connection file is written in
+///the directory. Returns what the code printed, or the error it raised
+pub fn run(
+    data: &DataHolder,
+    interpreter: &str,
+    kernel_name: &str,
+    dir: &str,
+    code: &str,
+    settings: &ProcessSettings,
+) -> Result<String, SniprunError> {
+    // a kernel per session, and one for the runs without a session
+    let connection_file = match (&data.interpreter_data, SessionKey::new(data, interpreter)) {
+        (None, _) => format!("{}/kernel_{}_run.json", dir, kernel_name),
+        (Some(_), SessionKey { buffer: None, .. }) => {
+            format!("{}/kernel_{}.json", dir, kernel_name)
+        }
+        (
+            Some(_),
+            SessionKey {
+                buffer: Some(buffer),
+                ..
+            },
+        ) => format!("{}/kernel_{}_{}.json", dir, kernel_name, buffer),
+    };
+    let connection_file = connection_file.as_str();
+    let kernel = crate::repl::process(
+        data,
+        interpreter,
+        kernel_name,
+        || {
+            let _ = std::fs::remove_file(connection_file);
+            let mut kernel = Command::new("jupyter-kernel");
+            kernel
+                .arg(String::from("--kernel=") + kernel_name)
+                .arg(String::from("--KernelManager.connection_file=") + connection_file);
+            kernel
+        },
+        settings,
+    )?;
+    // the kernel stays locked for the whole run, and the interpreter data is never locked meanwhile
+    let pid = kernel.lock().unwrap().id();
+    if let Some(interpreter_data) = &data.interpreter_data {
+        interpreter_data
+            .lock()
+            .unwrap()
+            .session(&SessionKey::new(data, interpreter))
+            .pid = Some(pid);
+    }
+    let kernel = kernel.lock().unwrap();
+
+    let mut client =
+        KernelClient::wait_for(connection_file, KERNEL_START_TIMEOUT).map_err(|e| {
+            SniprunError::CustomError(format!("jupyter kernel '{}': {}", kernel_name, e))
+        })?;
+    info!("[JUPYTER] connected to the kernel of {}", connection_file);
+    let mut error = None;
+    let output = job::supervise(data, kernel.id(), settings, |stream_to| {
+        let execution = client.execute(code, &mut |text| {
+            if let Some(nvim) = stream_to {
+                text.lines().for_each(|line| job::stream_line(nvim, line));
+            }
+        })?;
+        error = execution.error;
+        Ok(Output {
+            // as waitpid() reports an exit code
+            status: ExitStatus::from_raw(if error.is_some() { 1 << 8 } else { 0 }),
+            stdout: execution.stdout.into_bytes(),
+            stderr: execution.stderr.into_bytes(),
+        })
+    })?;
+    match error {
+        None => Ok(String::from_utf8(output.stdout).unwrap()),
+        Some(error) => Err(SniprunError::RuntimeError(error)),
+    }
+}
+
+///hex HMAC-SHA256 of the header, parent header, metadata and content, empty without a key
+fn sign(key: &[u8], parts: &[&[u8]]) -> String {
+    if key.is_empty() {
+        return String::new();
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+///the message in the frames, if it is well formed and correctly signed
+fn parse(key: &[u8], frames: &[Vec<u8>]) -> Option<Message> {
+    let start = frames.iter().position(|f| f == DELIMITER)? + 1;
+    let (signature, parts) = frames[start..].split_first()?;
+    if parts.len() < 4 {
+        return None;
+    }
+    let parts: Vec<&[u8]> = parts[..4].iter().map(|p| p.as_slice()).collect();
+    if sign(key, &parts).as_bytes() != signature.as_slice() {
+        info!("[JUPYTER] dropping a message with an invalid signature");
+        return None;
+    }
+    let header: Value = serde_json::from_slice(parts[0]).ok()?;
+    let parent: Value = serde_json::from_slice(parts[1]).ok()?;
+    Some(Message {
+        msg_type: header["msg_type"].as_str()?.to_string(),
+        parent_id: parent["msg_id"].as_str().unwrap_or_default().to_string(),
+        content: serde_json::from_slice(parts[3]).ok()?,
+    })
+}
+
+///tracebacks are colored with ANSI escapes for terminals
+fn strip_colors(line: &str) -> String {
+    String::from_utf8_lossy(&strip_ansi_escapes::strip(line.as_bytes()).unwrap_or_default())
+        .to_string()
+}
+
+///the time in UTC, as in the 'date' of message headers
+fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = secs.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    let in_day = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        in_day / 3600,
+        in_day / 60 % 60,
+        in_day % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+#[cfg(test)]
+mod test_jupyter {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn connection_file_and_dates() {
+        let info = ConnectionInfo::parse(
+            r#"{"shell_port": 5001, "iopub_port": 5002, "ip": "127.0.0.1", "key": "k",
+                "transport": "tcp", "signature_scheme": "hmac-sha256"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.shell_port, 5001);
+        assert_eq!(info.key, "k");
+        assert!(ConnectionInfo::parse(r#"{"transport": "ipc", "shell_port": 1}"#).is_err());
+
+        let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        assert_eq!(iso8601(time), "2023-11-14T22:13:20.123456Z");
+    }
+
+    #[test]
+    fn exchange_messages() {
+        // a fake kernel, that answers the request it gets on the shell socket
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let kernel = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&greeting()).unwrap();
+            let mut peer = [0u8; 64];
+            stream.read_exact(&mut peer).unwrap();
+            write_frame(&mut stream, &ready("ROUTER"), false, true).unwrap();
+            let (ready, _, command) = read_frame(&mut stream).unwrap();
+            assert!(command && ready.ends_with(b"DEALER"));
+            let mut request = vec![];
+            loop {
+                let (body, more, _) = read_frame(&mut stream).unwrap();
+                request.push(body);
+                if !more {
+                    break;
+                }
+            }
+            // sent back as is, with a long frame after it
+            for frame in request.iter() {
+                write_frame(&mut stream, frame, true, false).unwrap();
+            }
+            write_frame(&mut stream, &[b'x'; 300], false, false).unwrap();
+        });
+
+        let mut shell = Socket::connect("127.0.0.1", port, "DEALER").unwrap();
+        let key = b"secret";
+        let header = json!({"msg_id": "1", "msg_type": "status"}).to_string();
+        let parent = json!({"msg_id": "0"}).to_string();
+        let content = json!({"execution_state": "idle"}).to_string();
+        let parts: [&[u8]; 4] = [
+            header.as_bytes(),
+            parent.as_bytes(),
+            b"{}",
+            content.as_bytes(),
+        ];
+        let signature = sign(key, &parts);
+        shell
+            .send(&[
+                b"identity",
+                DELIMITER,
+                signature.as_bytes(),
+                parts[0],
+                parts[1],
+                parts[2],
+                parts[3],
+            ])
+            .unwrap();
+        let frames = shell.recv(Some(Duration::from_secs(5))).unwrap();
+        kernel.join().unwrap();
+
+        assert_eq!(frames.last().unwrap().len(), 300);
+        let message = parse(key, &frames[..frames.len() - 1]).unwrap();
+        assert_eq!(message.msg_type, "status");
+        assert_eq!(message.parent_id, "0");
+        assert_eq!(message.content["execution_state"], "idle");
+
+        // tampered with
+        let mut frames = frames;
+        frames[4] = json!({"msg_id": "2"}).to_string().into_bytes();
+        assert_eq!(parse(key, &frames), None);
+    }
+
+    #[test]
+    fn close_on_drop() {
+        // a fake kernel, that waits for the end of the connection
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let kernel = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&greeting()).unwrap();
+            let mut peer = [0u8; 64];
+            stream.read_exact(&mut peer).unwrap();
+            write_frame(&mut stream, &ready("ROUTER"), false, true).unwrap();
+            read_frame(&mut stream).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream.read(&mut [0u8; 1]).unwrap()
+        });
+
+        let shell = Socket::connect("127.0.0.1", port, "DEALER").unwrap();
+        drop(shell);
+        assert_eq!(kernel.join().unwrap(), 0);
+    }
+}
@@ -1,9 +1,14 @@
 use crate::*;
+use config::interpreter_options;
+use custom::CustomInterpreter;
 use error::SniprunError;
-use interpreter::{Interpreter, SupportLevel};
+use interpreter::{common_options, Interpreter, InterpreterOption, SupportLevel};
+use job::{Job, RunResult};
+use requirement::Requirement;
 use std::io::prelude::*;
 use std::process::Command;
 use std::{fs::File, io::Read};
+use treesitter::RangeMode;
 
 pub struct Launcher {
     pub data: DataHolder,
@@ -14,62 +19,414 @@ impl Launcher {
         Launcher { data }
     }
 
-    pub fn select_and_run<'a>(&self) -> Result<String, SniprunError> {
-        let selection = self.select();
-        if let Some((name, level)) = selection {
-            //launch !
-            iter_types! {
-                if Current::get_name() == name {
-                    info!("[LAUNCHER] Selected interpreter: {}, at level {}", name, level);
-                    let mut inter = Current::new_with_level(self.data.clone(), level);
-                    return inter.run();
+    pub fn select_and_run(&self) -> RunResult {
+        //the job records what the interpreter's processes do during the run
+        let mut data = self.data.clone();
+        let job = data.job.get_or_insert_with(Job::new).clone();
+        let result = self.run_selected(data);
+        let run = job.lock().unwrap().run_result(result);
+        run
+    }
+
+    fn run_selected(&self, data: DataHolder) -> Result<String, SniprunError> {
+        let candidates = self.candidates();
+        let modeline = &self.data.modeline_interpreter;
+        if !modeline.is_empty() && !candidates.iter().any(|(name, _)| name == modeline) {
+            return Err(SniprunError::CustomError(format!(
+                "There is no interpreter named '{}' (from the 'sniprun: interpreter=' modeline)",
+                modeline
+            )));
+        }
+        if candidates.is_empty() {
+            return Err(SniprunError::CustomError(String::from(
+                "No filetype set for current file, and none could be guessed from a shebang or the code",
+            )));
+        }
+
+        // the syntax node to run instead of the lines, for the interpreters that can use it
+        let mut node_data: Option<DataHolder> = None;
+
+        // when an interpreter can't run the code, try lower support levels, then the next interpreter
+        let mut attempts = vec![];
+        for (name, level) in candidates {
+            if let Err(e) = self.available(&name) {
+                info!("[LAUNCHER] skipping {}: {}", name, e);
+                let attempt = format!("{}: {}", name, e);
+                job::add_attempt(&data, &attempt);
+                attempts.push(attempt);
+                continue;
+            }
+            let data = if data.range_mode == RangeMode::Enclosing
+                || Self::has_treesitter_capability(&name)
+            {
+                if node_data.is_none() {
+                    let mut expanded = data.clone();
+                    treesitter::fetch_node(&mut expanded)?;
+                    node_data = Some(expanded);
+                }
+                node_data.clone().unwrap()
+            } else {
+                data.clone()
+            };
+            for level in Self::levels_to_try(&name, level) {
+                job::new_attempt(&data);
+                info!(
+                    "[LAUNCHER] Selected interpreter: {}, at level {}",
+                    name, level
+                );
+                let result = match self.custom(&name) {
+                    Some(custom) => custom.run(&data),
+                    None => Self::run_interpreter(&name, level, data.clone()),
+                };
+                match result {
+                    Err(e @ SniprunError::UnsufficientSupportLevel)
+                    | Err(e @ SniprunError::InterpreterLimitationError(_)) => {
+                        info!("[LAUNCHER] {} at level {} failed: {}", name, level, e);
+                        let attempt = format!("{} at level {}: {}", name, level, e);
+                        job::add_attempt(&data, &attempt);
+                        attempts.push(attempt);
+                    }
+                    _ => return result,
                 }
             }
-            info!("[LAUNCHER] Could not find a suitable interpreter");
-            return Err(SniprunError::CustomError(
-                "could not find/run the selected interpreter".to_owned(),
-            ));
-        } else {
-            return Err(SniprunError::CustomError(String::from(
-                "No filetype set for current file",
+        }
+        job::all_attempts_failed(&data);
+        Err(SniprunError::CustomError(format!(
+            "No interpreter could run this code, attempts:\n{}",
+            attempts.join("\n")
+        )))
+    }
+
+    fn run_interpreter(
+        name: &str,
+        level: SupportLevel,
+        data: DataHolder,
+    ) -> Result<String, SniprunError> {
+        iter_types! {
+            if Current::get_name() == name {
+                let mut inter = Current::new_with_level(data, level);
+                return inter.run();
+            }
+        }
+        info!("[LAUNCHER] Could not find a suitable interpreter");
+        Err(SniprunError::CustomError(
+            "could not find/run the selected interpreter".to_owned(),
+        ))
+    }
+
+    ///the interpreter of that name defined in the user config, if any
+    fn custom(&self, name: &str) -> Option<&CustomInterpreter> {
+        self.data
+            .custom_interpreters
+            .iter()
+            .find(|c| c.name == name)
+    }
+
+    ///what the interpreter of that name needs
+    fn requirements(&self, name: &str) -> Vec<Requirement> {
+        if let Some(custom) = self.custom(name) {
+            return custom.requirements();
+        }
+        iter_types! {
+            if Current::get_name() == name {
+                return Current::get_requirements();
+            }
+        }
+        vec![]
+    }
+
+    ///whether the executables the interpreter needs can be found
+    fn available(&self, name: &str) -> Result<(), SniprunError> {
+        let missing = requirement::missing(&self.requirements(name));
+        if !missing.is_empty() {
+            return Err(SniprunError::CustomError(format!(
+                "missing executable(s): {}",
+                missing.join(", ")
             )));
         }
+        Ok(())
+    }
+
+    fn has_treesitter_capability(name: &str) -> bool {
+        iter_types! {
+            if Current::get_name() == name {
+                return Current::has_treesitter_capability();
+            }
+        }
+        false
+    }
+
+    ///the given level, then the usual lower ones the interpreter can fall back to
+    fn levels_to_try(name: &str, level: SupportLevel) -> Vec<SupportLevel> {
+        let mut max_level = level;
+        iter_types! {
+            if Current::get_name() == name && Current::get_max_support_level() < max_level {
+                max_level = Current::get_max_support_level();
+            }
+        }
+        let lower_levels = [
+            SupportLevel::File,
+            SupportLevel::Import,
+            SupportLevel::Bloc,
+            SupportLevel::Line,
+        ];
+        std::iter::once(level)
+            .chain(lower_levels.iter().copied().filter(|l| *l < max_level))
+            .collect()
     }
 
+    ///The best interpreter for the filetype that can run, at its best support level
     pub fn select(&self) -> Option<(String, SupportLevel)> {
-        if self.data.filetype.is_empty() {
-            return None;
+        let candidates = self.candidates();
+        candidates
+            .iter()
+            .find(|(name, _)| self.available(name).is_ok())
+            .or_else(|| candidates.first())
+            .cloned()
+    }
+
+    ///Interpreters that support the filetype, in the order they should be tried: the one named by
+    ///a modeline, the ones selected by the user, the ones the code looks written for, the default
+    ///for the filetype, the ones of the 'fallback_order' list, then the others by decreasing
+    ///support level
+    pub fn candidates(&self) -> Vec<(String, SupportLevel)> {
+        if self.data.filetype.is_empty() && self.data.modeline_interpreter.is_empty() {
+            return vec![];
         }
+        let code = if self.data.current_bloc.trim().is_empty() {
+            &self.data.current_line
+        } else {
+            &self.data.current_bloc
+        };
 
-        let mut max_level_support = SupportLevel::Unsupported;
-        let mut name_best_interpreter = String::from("Generic");
-        //select the best interpreter for the language
-        let mut skip_all = false;
+        let mut candidates = vec![];
         iter_types! {
-            if !skip_all && Current::get_supported_languages().contains(&self.data.filetype){
-                if Current::get_max_support_level() > max_level_support {
-                    max_level_support = Current::get_max_support_level();
-                    name_best_interpreter = Current::get_name();
-                }
+            let name = Current::get_name();
+            let modeline = name == self.data.modeline_interpreter;
+            let supported = Current::get_supported_languages().contains(&self.data.filetype)
+                || Current::get_configured_languages(&self.data).contains(&self.data.filetype);
+            if modeline || supported {
+                let max_level = Current::get_max_support_level();
+                let selected = self.data.selected_interpreters.iter().position(|n| n == &name);
+                let ordered = self.data.fallback_order.iter().position(|n| n == &name);
+                let (rank, index, level) = match (selected, Current::matches_code(code), ordered) {
+                    _ if modeline => (0, 0, SupportLevel::Selected),
+                    (Some(i), _, _) => (1, i, SupportLevel::Selected),
+                    (None, Some(true), _) => (2, 0, max_level),
+                    (None, None, _) if Current::default_for_filetype() => (3, 0, max_level),
+                    // only what to fall back to, not what to run first
+                    (None, _, Some(i)) => (4, i, max_level),
+                    (None, None, None) => (5, 0, max_level),
+                    (None, Some(false), None) => (6, 0, max_level),
+                };
+                candidates.push((rank, index, std::cmp::Reverse(max_level as u8), name, level));
+            }
+        }
+        // the user defined them for these filetypes: before the default built-in interpreters
+        for custom in self.data.custom_interpreters.iter() {
+            let modeline = custom.name == self.data.modeline_interpreter;
+            if modeline || custom.supports(&self.data.filetype) {
+                let name = custom.name.clone();
+                let selected = self
+                    .data
+                    .selected_interpreters
+                    .iter()
+                    .position(|n| n == &name);
+                let (rank, index, level) = match selected {
+                    _ if modeline => (0, 0, SupportLevel::Selected),
+                    Some(i) => (1, i, SupportLevel::Selected),
+                    None => (2, 0, SupportLevel::Bloc),
+                };
+                candidates.push((rank, index, std::cmp::Reverse(u8::MAX), name, level));
+            }
+        }
+        candidates.sort_by(|a, b| (a.0, a.1, a.2, &a.3).cmp(&(b.0, b.1, b.2, &b.3)));
+        let mut candidates: Vec<(String, SupportLevel)> = candidates
+            .into_iter()
+            .map(|(_, _, _, name, level)| (name, level))
+            .collect();
+        if candidates.is_empty() && !self.data.filetype.is_empty() {
+            candidates.push((String::from("Generic"), SupportLevel::Unsupported));
+        }
+        candidates
+    }
 
-                if self.data.selected_interpreters.contains(&Current::get_name()){
-                    max_level_support = SupportLevel::Selected;
-                    name_best_interpreter = Current::get_name();
-                    skip_all = true;
+    ///Problems in the user's interpreter_options: unknown interpreters, unknown options
+    ///(typos) and options of the wrong type
+    pub fn check_interpreter_options(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let options = match self
+            .data
+            .interpreter_options
+            .as_ref()
+            .and_then(|o| o.as_map())
+        {
+            Some(options) => options,
+            None => return problems,
+        };
+        for (name, user_options) in options {
+            let name = name.as_str().unwrap_or_default();
+            let mut known = false;
+            iter_types! {
+                if Current::get_name() == name {
+                    known = true;
+                    problems.extend(Self::check_options(
+                        &Current::get_name(),
+                        Current::get_options(),
+                        user_options,
+                    ));
                 }
+            }
+            if let Some(custom) = self.custom(name) {
+                known = true;
+                problems.extend(Self::check_options(&custom.name, vec![], user_options));
+            }
+            if !known {
+                problems.push(format!(
+                    "interpreter_options: there is no interpreter named '{}'",
+                    name
+                ));
+            }
+        }
+        problems
+    }
+
+    fn check_options(
+        name: &str,
+        declared: Vec<InterpreterOption>,
+        user_options: &Value,
+    ) -> Vec<String> {
+        let declared: Vec<InterpreterOption> =
+            declared.into_iter().chain(common_options()).collect();
+        let mut problems = vec![];
+        for (key, value) in user_options.as_map().map(|m| m.as_slice()).unwrap_or(&[]) {
+            let key = key.as_str().unwrap_or_default();
+            match declared.iter().find(|option| option.name == key) {
+                Some(option) if !option.kind.matches(value) => problems.push(format!(
+                    "{}: option '{}' should be of type {}, got {}",
+                    name, key, option.kind, value
+                )),
+                Some(_) => (),
+                None => problems.push(format!(
+                    "{}: unknown option '{}', valid options are: {}",
+                    name,
+                    key,
+                    declared
+                        .iter()
+                        .map(|option| option.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            }
+        }
+        problems
+    }
 
-                if Current::default_for_filetype() {
-                    max_level_support = Current::get_max_support_level();
-                    name_best_interpreter = Current::get_name();
-                    skip_all = true;
+    ///documentation and valid options of an interpreter, for ':SnipInfo <name>'
+    fn interpreter_info(&self, name: &str) -> Vec<String> {
+        let mut v: Vec<String> = vec![];
+        iter_types! {
+            if Current::get_name().eq_ignore_ascii_case(name) {
+                let doc = self.data.sniprun_root_dir.clone() + "/doc/" + &Current::get_name() + ".md";
+                if let Ok(content) = std::fs::read_to_string(doc) {
+                    v.push(content);
+                }
+                v.push(format!(
+                    "Options, set with interpreter_options = {{ {} = {{ option = value }} }}:",
+                    Current::get_name()
+                ));
+                for option in Current::get_options().iter().chain(common_options().iter()) {
+                    v.push(format!("  {} ({}): {}", option.name, option.kind, option.description));
+                }
+                if !Current::get_requirements().is_empty() {
+                    v.push(String::from("Requirements:"));
                 }
+                for requirement in Current::get_requirements() {
+                    v.push(format!("  {}", requirement.status()));
+                }
+                let name = Current::get_name();
+                if let Some(user_options) = interpreter_options(&self.data.interpreter_options, &name) {
+                    for problem in Self::check_options(&name, Current::get_options(), user_options) {
+                        v.push(format!("Warning: {}", problem));
+                    }
+                }
+                return v;
+            }
+        }
+        if let Some(custom) = self
+            .data
+            .custom_interpreters
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+        {
+            v.extend(custom.describe());
+            v.push(String::from("Requirements:"));
+            for requirement in custom.requirements() {
+                v.push(format!("  {}", requirement.status()));
             }
+            return v;
+        }
+        v.push(format!("Unknown interpreter: {}", name));
+        v
+    }
+
+    ///For the health check: each interpreter, and its found or missing executables
+    pub fn requirements_report(&self) -> Vec<String> {
+        let mut interpreters: Vec<(String, Vec<Requirement>)> = self
+            .data
+            .custom_interpreters
+            .iter()
+            .map(|custom| (custom.name.clone(), custom.requirements()))
+            .collect();
+        iter_types! {
+            interpreters.push((Current::get_name(), Current::get_requirements()));
+        }
+        // a version probe can take up to its timeout: they all run at the same time
+        let probes: Vec<(String, Vec<std::thread::JoinHandle<String>>)> = interpreters
+            .into_iter()
+            .filter(|(_, requirements)| !requirements.is_empty())
+            .map(|(name, requirements)| {
+                let probes = requirements
+                    .into_iter()
+                    .map(|r| std::thread::spawn(move || r.status()))
+                    .collect();
+                (name, probes)
+            })
+            .collect();
+        let mut report: Vec<String> = probes
+            .into_iter()
+            .map(|(name, probes)| {
+                let statuses: Vec<String> = probes
+                    .into_iter()
+                    .map(|p| p.join().unwrap_or_else(|_| String::from("could not probe")))
+                    .collect();
+                format!("{}: {}", name, statuses.join(", "))
+            })
+            .collect();
+        report.sort();
+        report
+    }
+
+    pub fn info(&self, interpreter: Option<&str>) -> std::io::Result<String> {
+        let v = match interpreter {
+            Some(name) => self.interpreter_info(name),
+            None => self.general_info()?,
+        };
+
+        if self.data.return_message_type == ReturnMessageType::Multiline {
+            info!("[INFO] Returning info directly");
+            return Ok(v.join("\n"));
+        } else {
+            //write to infofile
+            info!("[INFO] Writing info to file");
+            let filename = self.data.sniprun_root_dir.clone() + "/ressources/infofile.txt";
+            let mut file = File::create(filename).unwrap();
+            file.write_all(v.join("\n").as_bytes()).unwrap();
+            return Ok("".to_owned());
         }
-        let _ = skip_all; //silence false unused variable warning
-        return Some((name_best_interpreter, max_level_support));
     }
 
-    pub fn info(&self) -> std::io::Result<String> {
+    fn general_info(&self) -> std::io::Result<Vec<String>> {
         let mut v: Vec<String> = vec![];
         let filename = self.data.sniprun_root_dir.clone() + "/ressources/asciiart.txt";
 
@@ -127,6 +484,19 @@ impl Launcher {
             temp_vec.push(line);
         }
 
+        for custom in self.data.custom_interpreters.iter() {
+            temp_vec.push(format!(
+                "| {:<25}| {:<13}| {:<14}|{:^13}|{:^12}|{:^14}|{:^12}|",
+                custom.name.clone() + " (custom)",
+                custom.filetypes.first().cloned().unwrap_or_default(),
+                SupportLevel::Bloc.to_string(),
+                "yes",
+                "no",
+                "no",
+                "no"
+            ));
+        }
+
         temp_vec.sort();
 
         for (i, line) in temp_vec.iter().enumerate() {
@@ -138,17 +508,7 @@ impl Launcher {
 
         v.push(separator.clone());
 
-        if self.data.return_message_type == ReturnMessageType::Multiline {
-            info!("[INFO] Returning info directly");
-            return Ok(v.join("\n"));
-        } else {
-            //write to infofile
-            info!("[INFO] Writing info to file");
-            let filename = self.data.sniprun_root_dir.clone() + "/ressources/infofile.txt";
-            let mut file = File::create(filename).unwrap();
-            file.write_all(v.join("\n").as_bytes()).unwrap();
-            return Ok("".to_owned());
-        }
+        Ok(v)
     }
 }
 
@@ -170,6 +530,110 @@ mod test_launcher {
         let _res = launcher.select();
     }
 
+    #[test]
+    fn candidates_order() {
+        let mut data = DataHolder::new();
+        data.filetype = String::from("python");
+        let launcher = Launcher::new(data.clone());
+        let names: Vec<String> = launcher.candidates().into_iter().map(|c| c.0).collect();
+        assert_eq!(names[0], "Python3_original");
+        assert!(names.contains(&String::from("Python3_jupyter")));
+
+        // the default still runs first, the list only orders the fallbacks
+        data.fallback_order = vec![String::from("Python3_jupyter")];
+        let launcher = Launcher::new(data);
+        let candidates = launcher.candidates();
+        assert_eq!(candidates[0].0, "Python3_original");
+        assert_eq!(
+            candidates[1],
+            (String::from("Python3_jupyter"), SupportLevel::Import)
+        );
+    }
+
+    #[test]
+    fn code_and_modeline() {
+        let mut data = DataHolder::new();
+        data.filetype = String::from("lua");
+        data.current_bloc = String::from("vim.notify('hi')");
+        let launcher = Launcher::new(data.clone());
+        assert_eq!(launcher.candidates()[0].0, "Lua_nvim");
+        assert_eq!(launcher.candidates().last().unwrap().0, "Lua_original");
+
+        data.current_bloc = String::from("print('hi')");
+        let launcher = Launcher::new(data.clone());
+        assert_eq!(launcher.candidates()[0].0, "Lua_original");
+
+        data.filetype = String::new();
+        data.modeline_interpreter = String::from("Bash_original");
+        let launcher = Launcher::new(data.clone());
+        assert_eq!(
+            launcher.candidates(),
+            vec![(String::from("Bash_original"), SupportLevel::Selected)]
+        );
+
+        data.modeline_interpreter = String::from("Bahs_original");
+        let run = Launcher::new(data).select_and_run();
+        assert!(run
+            .result
+            .unwrap_err()
+            .to_string()
+            .contains("no interpreter named 'Bahs_original'"));
+    }
+
+    #[test]
+    fn fallback_attempts() {
+        let mut data = DataHolder::new();
+        data.filetype = String::from("javascript");
+        data.current_bloc = String::from("console.log(1)");
+        // JS_original has no REPL-like behavior
+        data.repl_enabled = vec![String::from("JS_original")];
+        let run = Launcher::new(data).select_and_run();
+        let message = run.result.clone().unwrap_err().to_string();
+        assert!(message.starts_with("No interpreter could run this code"));
+        assert!(message.contains("JS_original at level Bloc"));
+        assert!(message.contains("JS_original at level Line"));
+        assert_eq!(run.attempts.len(), 2);
+        assert!(run.all_attempts_failed);
+        // the attempts are not listed twice
+        assert_eq!(run.detailed().unwrap_err().to_string(), message);
+    }
+
+    #[test]
+    fn check_options() {
+        let mut data = DataHolder::new();
+        let options = |entries: Vec<(&str, Value)>| {
+            Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (Value::from(k), v))
+                    .collect(),
+            )
+        };
+        data.interpreter_options = Some(options(vec![
+            (
+                "GFM_original",
+                options(vec![
+                    ("default_filetype", Value::from("bash")),
+                    ("default_filetpye", Value::from("bash")),
+                    ("timeout", Value::from("10")),
+                ]),
+            ),
+            ("Pyhton3_original", options(vec![])),
+        ]));
+
+        let problems = Launcher::new(data).check_interpreter_options();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("GFM_original: unknown option 'default_filetpye'"));
+        assert_eq!(
+            problems[1],
+            "GFM_original: option 'timeout' should be of type integer, got \"10\""
+        );
+        assert_eq!(
+            problems[2],
+            "interpreter_options: there is no interpreter named 'Pyhton3_original'"
+        );
+    }
+
     #[test]
     fn info() {
         let mut data = DataHolder::new();
@@ -182,6 +646,13 @@ mod test_launcher {
         data.range = [1, 1];
 
         let launcher = Launcher::new(data);
-        let _res = launcher.info().unwrap();
+        let _res = launcher.info(None).unwrap();
+
+        let interpreter_info = launcher.info(Some("Bash_original")).unwrap();
+        assert!(interpreter_info.contains("Requirements:\n  found "));
+        let report = launcher.requirements_report();
+        assert!(report
+            .iter()
+            .any(|line| line.starts_with("Bash_original: found ")));
     }
 }
@@ -9,13 +9,29 @@ use simple_logging::log_to_file;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use display::{DisplayType,display, return_message_classic};
-use std::str::FromStr;
+use job::{Job, RunResult};
+use config::SniprunConfig;
+use error::SniprunError;
+use treesitter::RangeMode;
+use std::collections::{HashMap, HashSet};
 
 mod error;
 mod interpreter;
 mod interpreters;
 mod launcher;
 mod display;
+mod job;
+mod directive;
+mod stdin;
+mod environment;
+mod project;
+mod config;
+mod treesitter;
+mod requirement;
+mod custom;
+mod detect;
+mod repl;
+mod jupyter;
 
 ///This struct holds (with ownership) the data Sniprun and neovim
 ///give to the interpreter.
@@ -33,11 +49,13 @@ pub struct DataHolder {
 
     ///The inclusive limits of the selected block (line numbers)
     range: [i64; 2],
+    ///whether to run the selection or the function/block around it (see treesitter::RangeMode)
+    range_mode: RangeMode,
     /// path of the current file that's being edited
     filepath: String,
-    /// Field is left blank as of v0.3
+    /// root of the project of the current file (see project::find_root), empty if none was found
     projectroot: String,
-    /// field is left blank as of v0.3
+    /// dependencies of that project: virtualenv site-packages, node_modules, cargo target dir...
     dependencies_path: Vec<String>,
     /// path to the cache directory that sniprun create
     work_dir: String,
@@ -53,12 +71,35 @@ pub struct DataHolder {
     repl_enabled: Vec<String>,
     ///user config: repl behavior disabled list of interpreters
     repl_disabled: Vec<String>,
-    ///interpreter options
+    ///user config: interpreters to try, in this order, when the best one can't run the code
+    fallback_order: Vec<String>,
+    ///user config: options of each interpreter, a map of interpreter name -> map of options
     interpreter_options: Option<Value>,
+    ///user config: interpreters defined by the user, that run command templates
+    custom_interpreters: Vec<custom::CustomInterpreter>,
+    ///interpreter named by a 'sniprun: interpreter=...' modeline, tried first, empty if none
+    modeline_interpreter: String,
+    ///number of the current buffer
+    buffer: i64,
+    ///user config: whether each buffer has its own REPL sessions
+    repl_per_buffer: bool,
 
     ///interpreter data
     interpreter_data: Option<Arc<Mutex<InterpreterData>>>,
 
+    ///processes spawned by the current run, so it can be stopped
+    job: Option<Arc<Mutex<Job>>>,
+    ///user config: default timeout (in seconds, 0 = none) of the processes spawned by interpreters
+    timeout: u64,
+    ///where the standard input of the user code comes from (see stdin::StdinSource), empty for /dev/null
+    stdin: String,
+    ///user config: working directory of the spawned processes: "file", "project" or a path
+    cwd: String,
+    ///user config: extra environment variables of the spawned processes
+    env: Vec<(String, String)>,
+    ///user config: .env file to load, relative to the working directory
+    env_file: String,
+
     /// whether to display echomsg-based messages (more compatibility)
     /// or new ones (multiline support) (default)
     return_message_type: ReturnMessageType,
@@ -70,13 +111,24 @@ pub struct DataHolder {
 #[derive(Clone, Default, Debug)]
 ///data that can be saved/accessed between Arc 2 interpreter runs
 pub struct InterpreterData {
-    ///indentifies the current interpreter (so that data from another interpreter does not get used
-    owner: String,
-    ///actual data, usually previous code selection for repl behavior
-    content: String,
+    ///REPL sessions, by interpreter and buffer (see repl::SessionKey)
+    sessions: HashMap<repl::SessionKey, repl::Session>,
+    ///directories of the projects whose saved sessions were restored (see repl::sessions_dir)
+    restored_projects: HashSet<String>,
+}
+
+impl InterpreterData {
+    ///the session of that key, empty if it was not used yet
+    fn session(&mut self, key: &repl::SessionKey) -> &mut repl::Session {
+        self.sessions.entry(key.clone()).or_default()
+    }
 
-    /// PID of linked REPL if existing
-    pid: Option<u32>,
+    ///forget the sessions of the target and stop their processes, returns how many there were
+    fn clear(&mut self, target: &repl::ClearTarget) -> usize {
+        let count = self.sessions.len();
+        self.sessions.retain(|key, _| !target.matches(key));
+        count - self.sessions.len()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -100,6 +152,7 @@ impl DataHolder {
             current_line: String::new(),
             current_bloc: String::new(),
             range: [-1, -1],
+            range_mode: RangeMode::Selection,
             filepath: String::new(),
             projectroot: String::new(),
             dependencies_path: vec![],
@@ -109,8 +162,19 @@ impl DataHolder {
             selected_interpreters: vec![],
             repl_enabled: vec![],
             repl_disabled: vec![],
+            fallback_order: vec![],
             interpreter_options: None,
+            custom_interpreters: vec![],
+            modeline_interpreter: String::new(),
+            buffer: 0,
+            repl_per_buffer: false,
             interpreter_data: None,
+            job: None,
+            timeout: 0,
+            stdin: String::new(),
+            cwd: String::from("file"),
+            env: vec![],
+            env_file: String::new(),
             return_message_type: ReturnMessageType::Multiline,
             display_type: vec![DisplayType::Classic],
         }
@@ -132,10 +196,13 @@ struct EventHandler {
 
 enum Messages {
     Run,
+    Stop,
     Clean,
     ClearReplMemory,
+    DiscardSessions,
     Info,
     Ping,
+    Health,
     Unknown(String),
 }
 
@@ -143,10 +210,13 @@ impl From<String> for Messages {
     fn from(event: String) -> Self {
         match &event[..] {
             "run" => Messages::Run,
+            "stop" => Messages::Stop,
             "clean" => Messages::Clean,
             "clearrepl" => Messages::ClearReplMemory,
+            "discardsessions" => Messages::DiscardSessions,
             "ping" => Messages::Ping,
             "info" => Messages::Info,
+            "health" => Messages::Health,
             _ => Messages::Unknown(event),
         }
     }
@@ -157,11 +227,7 @@ impl EventHandler {
         let session = Session::new_parent().unwrap();
         let nvim = Neovim::new(session);
         let mut data = DataHolder::new();
-        let interpreter_data = Arc::new(Mutex::new(InterpreterData {
-            owner: String::new(),
-            content: String::new(),
-            pid: None,
-        }));
+        let interpreter_data = Arc::new(Mutex::new(InterpreterData::default()));
         data.interpreter_data = Some(interpreter_data.clone());
 
         EventHandler {
@@ -171,31 +237,38 @@ impl EventHandler {
         }
     }
 
-    fn index_from_name(&mut self, name: &str, config: &Vec<(Value, Value)>) -> usize {
-        for (i, kv) in config.iter().enumerate() {
-            if name == kv.0.as_str().unwrap() {
-                return i;
-            }
-        }
-        info!("key not found");
-        return 0;
-    }
-
     /// fill the DataHolder with data from sniprun and Neovim
-    fn fill_data(&mut self, values: Vec<Value>) {
+    fn fill_data(&mut self, values: Vec<Value>) -> Result<(), SniprunError> {
         // info!("[FILLDATA] received data from RPC: {:?}", values);
-        let config = values[2].as_map().unwrap();
+        let config = values.get(2).ok_or_else(|| {
+            SniprunError::InternalError(String::from("No configuration received from neovim"))
+        })?;
+        let (config, warnings) = SniprunConfig::from_value(config)?;
+        for warning in warnings {
+            info!("[FILLDATA] {}", warning);
+            let _ = self.nvim.lock().unwrap().command(&format!(
+                "echohl WarningMsg | echomsg \"[Sniprun] {}\" | echohl None",
+                warning.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
         {
             self.data.interpreter_data = Some(self.interpreter_data.clone());
             info!("[FILLDATA] got back eventual interpreter data");
         }
 
         {
-            self.data.range = [values[0].as_i64().unwrap(), values[1].as_i64().unwrap()];
+            let line = |i: usize| {
+                values.get(i).and_then(Value::as_i64).ok_or_else(|| {
+                    SniprunError::InternalError(format!(
+                        "Invalid range received from neovim: {:?}",
+                        &values[..values.len().min(2)]
+                    ))
+                })
+            };
+            self.data.range = [line(0)?, line(1)?];
         }
         {
-            let i = self.index_from_name("sniprun_root_dir", config);
-            self.data.sniprun_root_dir = String::from(config[i].1.as_str().unwrap());
+            self.data.sniprun_root_dir = config.sniprun_root_dir.clone();
             info!("[FILLDATA] got sniprun root");
         }
 
@@ -203,7 +276,7 @@ impl EventHandler {
             //get filetype
             let ft = self.nvim.lock().unwrap().command_output("set ft?");
             if let Ok(real_ft) = ft {
-                self.data.filetype = String::from(real_ft.split("=").last().unwrap());
+                self.data.filetype = String::from(real_ft.rsplit('=').next().unwrap_or_default());
             }
         }
 
@@ -219,7 +292,10 @@ impl EventHandler {
         {
             //get current bloc
             let mut nvim_instance = self.nvim.lock().unwrap();
-            let current_bloc = nvim_instance.get_current_buf().unwrap().get_lines(
+            let buffer = nvim_instance.get_current_buf().map_err(|e| {
+                SniprunError::InternalError(format!("Could not get the current buffer: {}", e))
+            })?;
+            let current_bloc = buffer.get_lines(
                 &mut nvim_instance,
                 self.data.range[0] - 1, //because the function is 0-based instead of 1 and end-exclusive
                 self.data.range[1],
@@ -231,6 +307,40 @@ impl EventHandler {
             info!("[FILLDATA] got current_bloc");
         }
 
+        {
+            // like vim modelines, 'sniprun: interpreter=...' can be in the code or at the
+            // start or end of the file; the shebang is in the first line of the file
+            let mut nvim_instance = self.nvim.lock().unwrap();
+            let buffer = nvim_instance.get_current_buf().map_err(|e| {
+                SniprunError::InternalError(format!("Could not get the current buffer: {}", e))
+            })?;
+            self.data.buffer = buffer.get_number(&mut nvim_instance).unwrap_or_default();
+            let head = buffer
+                .get_lines(&mut nvim_instance, 0, 5, false)
+                .unwrap_or_default();
+            let tail = buffer
+                .get_lines(&mut nvim_instance, -6, -1, false)
+                .unwrap_or_default();
+            self.data.modeline_interpreter =
+                directive::find(&self.data.current_bloc, "interpreter")
+                    .or_else(|| directive::find(&head.join("\n"), "interpreter"))
+                    .or_else(|| directive::find(&tail.join("\n"), "interpreter"))
+                    .unwrap_or_default();
+            if self.data.filetype.is_empty() {
+                let code = if self.data.current_bloc.trim().is_empty() {
+                    &self.data.current_line
+                } else {
+                    &self.data.current_bloc
+                };
+                let first_line = head.first().map(String::as_str).unwrap_or_default();
+                self.data.filetype = detect::filetype(first_line, code).unwrap_or_default();
+            }
+            info!(
+                "[FILLDATA] got modeline interpreter: {:?}, filetype: {:?}",
+                self.data.modeline_interpreter, self.data.filetype
+            );
+        }
+
         {
             //get full file path
             let full_file_path = self
@@ -244,63 +354,38 @@ impl EventHandler {
             info!("[FILLDATA] got filepath");
         }
 
+        {
+            let markers = &config.project_root_markers;
+            if let Some(root) = project::find_root(&self.data.filepath, markers) {
+                self.data.dependencies_path = project::dependencies(&root);
+                self.data.projectroot = root.to_string_lossy().to_string();
+            }
+            info!(
+                "[FILLDATA] got project root: {:?}, dependencies: {:?}",
+                self.data.projectroot, self.data.dependencies_path
+            );
+        }
+
         {
             //get nvim instance
             self.data.nvim_instance = Some(self.nvim.clone());
             info!("[FILLDATA] got nvim_instance");
         }
         {
-            let i = self.index_from_name("selected_interpreters", config);
-            self.data.selected_interpreters = config[i]
-                .1
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|v| v.as_str().unwrap().to_owned())
-                .collect();
-            info!("[FILLDATA] got selected interpreters");
-        }
-        {
-            let i = self.index_from_name("repl_enable", config);
-            self.data.repl_enabled = config[i]
-                .1
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|v| v.as_str().unwrap().to_owned())
-                .collect();
-            info!("[FILLDATA] got repl enabled interpreters");
+            self.data.selected_interpreters = config.selected_interpreters.clone();
+            self.data.repl_enabled = config.repl_enable.clone();
+            self.data.repl_disabled = config.repl_disable.clone();
+            self.data.repl_per_buffer = config.repl_per_buffer;
+            self.data.fallback_order = config.fallback_order.clone();
+            info!("[FILLDATA] got selected and repl enabled/disabled interpreters");
         }
         {
-            let i = self.index_from_name("repl_disable", config);
-            self.data.repl_disabled = config[i]
-                .1
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|v| v.as_str().unwrap().to_owned())
-                .collect();
-            info!("[FILLDATA] got repl disabled interpreters");
-        }
-        {
-            let i = self.index_from_name("display", config);
-            self.data.display_type = config[i]
-                .1
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|v| v.as_str().unwrap())
-                .map(|v| DisplayType::from_str(v))
-                .inspect(|x| info!("[FILLDATA] display type found : {:?}", x))
-                .filter(|x| x.is_ok())
-                .map(|x| x.unwrap())
-                .collect();
-            info!("[FILLDATA] got display types");
+            self.data.display_type = config.display.clone();
+            info!("[FILLDATA] got display types: {:?}", self.data.display_type);
         }
 
         {
-            let i = self.index_from_name("inline_messages", config);
-            if config[i].1.as_i64().unwrap_or(0) == 1 {
+            if config.inline_messages {
                 self.data.return_message_type = ReturnMessageType::EchoMsg;
             } else {
                 self.data.return_message_type = ReturnMessageType::Multiline;
@@ -309,14 +394,36 @@ impl EventHandler {
         }
 
         {
-            self.data.interpreter_options = Some(values[2].clone());
+            self.data.timeout = config.timeout;
+            info!("[FILLDATA] got timeout setting");
+        }
+
+        {
+            // an inline 'sniprun: stdin=...' directive takes precedence over the config
+            self.data.stdin = directive::find(&self.data.current_bloc, "stdin")
+                .unwrap_or_else(|| config.stdin.clone());
+            info!("[FILLDATA] got stdin: {}", self.data.stdin);
+        }
+
+        {
+            self.data.cwd = config.cwd.clone();
+            self.data.env_file = config.env_file.clone();
+            self.data.env = config.env.clone();
+            info!("[FILLDATA] got cwd and environment settings");
+        }
+
+        {
+            self.data.interpreter_options = Some(config.interpreter_options);
+            self.data.custom_interpreters = config.custom_interpreters;
         }
 
         info!("[FILLDATA] Done!");
+        Ok(())
     }
 }
 enum HandleAction {
-    New(thread::JoinHandle<()>),
+    New(thread::JoinHandle<()>, Arc<Mutex<Job>>),
+    Stop,
 }
 
 fn main() {
@@ -337,14 +444,24 @@ fn main() {
 
     let (send, recv) = mpsc::channel();
     thread::spawn(move || {
-        let mut _handle: Option<thread::JoinHandle<()>> = None;
+        let mut running: Vec<(thread::JoinHandle<()>, Arc<Mutex<Job>>)> = vec![];
         loop {
             match recv.recv() {
                 Err(_) => {
                     info!("[MAIN] Broken connection");
                     panic!("Broken connection")
                 }
-                Ok(HandleAction::New(new)) => _handle = Some(new),
+                Ok(HandleAction::New(new, job)) => {
+                    running.retain(|(handle, _)| !handle.is_finished());
+                    running.push((new, job));
+                }
+                Ok(HandleAction::Stop) => {
+                    running.retain(|(handle, _)| !handle.is_finished());
+                    info!("[MAIN] Stopping {} running job(s)", running.len());
+                    for (_, job) in running.iter() {
+                        job.lock().unwrap().cancel();
+                    }
+                }
             }
         }
     });
@@ -359,38 +476,83 @@ fn main() {
 
                 let mut event_handler2 = event_handler.clone();
                 info!("[RUN] clone event handler");
+                let job = Job::new();
+                event_handler2.data.job = Some(job.clone());
+                // 'enclosing' to run the function or block around the cursor
+                event_handler2.data.range_mode = values
+                    .get(3)
+                    .and_then(|v| v.as_str())
+                    .map(RangeMode::from)
+                    .unwrap_or(RangeMode::Selection);
                 let _res2 = send.send(HandleAction::New(thread::spawn(move || {
                     // get up-to-date data
                     //
                     info!("[RUN] spawned thread");
-                    event_handler2.fill_data(values);
+                    if let Err(e) = event_handler2.fill_data(values) {
+                        info!("[RUN] could not fill dataholder: {}", e);
+                        let result = RunResult::from_result(Err(e));
+                        display(result, event_handler2.nvim, &event_handler2.data);
+                        return;
+                    }
                     info!("[RUN] filled dataholder");
 
+                    repl::restore_sessions(&event_handler2.data);
+
                     //run the launcher (that selects, init and run an interpreter)
                     let launcher = launcher::Launcher::new(event_handler2.data.clone());
                     info!("[RUN] created launcher");
                     let result = launcher.select_and_run();
                     info!("[RUN] Interpreter return a result");
+                    repl::save_sessions(&event_handler2.data);
 
                     display(result, event_handler2.nvim, &event_handler2.data);
                     
                     //clean data
                     event_handler2.data = DataHolder::new();
-                })));
+                }), job));
+            }
+            Messages::Stop => {
+                info!("[MAINLOOP] Stop command received");
+                let _res = send.send(HandleAction::Stop);
             }
             Messages::Clean => {
                 info!("[MAINLOOP] Clean command received");
                 event_handler.data.clean_dir()
             }
             Messages::ClearReplMemory => {
-                info!("[MAINLOOP] ClearReplMemory command received");
-                event_handler.interpreter_data.lock().unwrap().owner.clear();
-                event_handler
+                // ':SnipReplMemoryClean [all|buffer|<interpreter>]', and the current buffer
+                let target = repl::ClearTarget::new(
+                    values.first().and_then(|v| v.as_str()).unwrap_or_default(),
+                    values.get(1).and_then(|v| v.as_i64()).unwrap_or_default(),
+                );
+                info!("[MAINLOOP] ClearReplMemory command received: {:?}", target);
+                // stops the REPL processes of the sessions
+                let cleared = event_handler
                     .interpreter_data
                     .lock()
                     .unwrap()
-                    .content
-                    .clear();
+                    .clear(&target);
+                info!("[MAINLOOP] cleared {} REPL session(s)", cleared);
+            }
+            Messages::DiscardSessions => {
+                info!("[MAINLOOP] DiscardSessions command received");
+                // the project is the one of the current file
+                let mut event_handler2 = event_handler.clone();
+                let result = event_handler2.fill_data(values).and_then(|_| {
+                    repl::discard_sessions(&event_handler2.data).map_err(|e| {
+                        SniprunError::CustomError(format!(
+                            "could not discard the saved REPL sessions: {}",
+                            e
+                        ))
+                    })
+                });
+                if result.is_err() {
+                    return_message_classic(
+                        &result.map(|_| String::new()),
+                        &event_handler2.nvim,
+                        &ReturnMessageType::Multiline,
+                    );
+                }
             }
             Messages::Ping => {
                 info!("[MAINLOOP] Ping command received");
@@ -399,9 +561,22 @@ fn main() {
             Messages::Info => {
                 info!("[MAINLOOP] Info command received");
                 let mut event_handler2 = event_handler.clone();
-                event_handler2.fill_data(values);
+                // ':SnipInfo <interpreter>' gives the name of an interpreter
+                let interpreter = values
+                    .get(3)
+                    .and_then(|v| v.as_str())
+                    .map(String::from)
+                    .filter(|name| !name.is_empty());
+                if let Err(e) = event_handler2.fill_data(values) {
+                    return_message_classic(
+                        &Err(e),
+                        &event_handler2.nvim,
+                        &ReturnMessageType::Multiline,
+                    );
+                    continue;
+                }
                 let launcher = launcher::Launcher::new(event_handler2.data.clone());
-                let result = launcher.info();
+                let result = launcher.info(interpreter.as_deref());
                 if let Ok(infomsg) = result {
                     return_message_classic(
                         &Ok(infomsg),
@@ -411,6 +586,42 @@ fn main() {
                 }
             }
 
+            Messages::Health => {
+                info!("[MAINLOOP] Health command received");
+                // the report is read by the health check on the lua side
+                let mut data = event_handler.data.clone();
+                let config = values.get(2).ok_or_else(|| {
+                    SniprunError::InternalError(String::from(
+                        "No configuration received from neovim",
+                    ))
+                });
+                let problems = match config.and_then(SniprunConfig::from_value) {
+                    Ok((config, mut warnings)) => {
+                        data.interpreter_options = Some(config.interpreter_options);
+                        data.custom_interpreters = config.custom_interpreters;
+                        warnings.extend(
+                            launcher::Launcher::new(data.clone()).check_interpreter_options(),
+                        );
+                        warnings
+                    }
+                    Err(e) => vec![e.to_string()],
+                };
+                let report = format!("{}/{}", event_handler.data.work_dir, "health.txt");
+                let tmp_report = report.clone() + ".tmp";
+                if std::fs::write(&tmp_report, problems.join("\n")).is_ok() {
+                    let _ = std::fs::rename(tmp_report, report);
+                }
+                // probing the versions of the tools takes a while
+                let tools_report = format!("{}/{}", event_handler.data.work_dir, "tools.txt");
+                thread::spawn(move || {
+                    let tmp_report = tools_report.clone() + ".tmp";
+                    let tools = launcher::Launcher::new(data).requirements_report();
+                    if std::fs::write(&tmp_report, tools.join("\n")).is_ok() {
+                        let _ = std::fs::rename(tmp_report, tools_report);
+                    }
+                });
+            }
+
             Messages::Unknown(event) => {
                 info!("[MAINLOOP] Unknown event received: {:?}", event);
             }
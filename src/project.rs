@@ -0,0 +1,121 @@
+//! Detection of the project the current file belongs to, and of its dependencies
+
+use std::path::{Path, PathBuf};
+
+///files or directories whose presence marks the root of a project
+pub const DEFAULT_MARKERS: [&str; 7] = [
+    ".git",
+    "Cargo.toml",
+    "pyproject.toml",
+    "go.mod",
+    "package.json",
+    "pom.xml",
+    "build.gradle",
+];
+
+///closest directory above the file that contains one of the default or extra markers
+pub fn find_root(filepath: &str, extra_markers: &[String]) -> Option<PathBuf> {
+    let markers: Vec<&str> = DEFAULT_MARKERS
+        .iter()
+        .copied()
+        .chain(extra_markers.iter().map(String::as_str))
+        .collect();
+    Path::new(filepath)
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| markers.iter().any(|marker| dir.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
+
+///closest file with the given name in the directories above the file
+pub fn nearest(filepath: &str, name: &str) -> Option<PathBuf> {
+    Path::new(filepath)
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+///where the dependencies of the project live: virtualenv site-packages, node_modules, cargo target dir
+pub fn dependencies(root: &Path) -> Vec<String> {
+    let mut deps = vec![];
+
+    let mut venvs: Vec<PathBuf> = [".venv", "venv", "env"]
+        .iter()
+        .map(|name| root.join(name))
+        .collect();
+    if let Some(active) = std::env::var_os("VIRTUAL_ENV") {
+        venvs.insert(0, PathBuf::from(active));
+    }
+    for venv in venvs {
+        // site-packages is in lib/pythonX.Y/ on unix
+        if let Ok(entries) = std::fs::read_dir(venv.join("lib")) {
+            for entry in entries.flatten() {
+                let site_packages = entry.path().join("site-packages");
+                if site_packages.is_dir() {
+                    deps.push(site_packages);
+                }
+            }
+        }
+    }
+
+    let node_modules = root.join("node_modules");
+    if node_modules.is_dir() {
+        deps.push(node_modules);
+    }
+
+    if root.join("Cargo.toml").exists() {
+        let target = std::env::var_os("CARGO_TARGET_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| root.join("target"));
+        if target.is_dir() {
+            deps.push(target);
+        }
+    }
+
+    deps.iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test_project {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn detect_root_and_dependencies() {
+        let root = std::env::temp_dir().join("sniprun_project_test");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(root.join("src/module")).unwrap();
+        create_dir_all(root.join("node_modules")).unwrap();
+        create_dir_all(root.join(".venv/lib/python3.9/site-packages")).unwrap();
+        write(root.join("package.json"), "{}").unwrap();
+        let file = root.join("src/module/main.js");
+        let file = file.to_str().unwrap();
+
+        assert_eq!(find_root(file, &[]), Some(root.clone()));
+        write(root.join("src/.sniprun_root"), "").unwrap();
+        assert_eq!(
+            find_root(file, &[String::from(".sniprun_root")]),
+            Some(root.join("src"))
+        );
+
+        assert_eq!(
+            nearest(file, "package.json"),
+            Some(root.join("package.json"))
+        );
+        assert_eq!(nearest(file, "Cargo.toml"), None);
+
+        let deps = dependencies(&root);
+        assert!(deps.contains(&root.join("node_modules").to_string_lossy().to_string()));
+        assert!(deps.contains(
+            &root
+                .join(".venv/lib/python3.9/site-packages")
+                .to_string_lossy()
+                .to_string()
+        ));
+    }
+}
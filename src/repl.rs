@@ -0,0 +1,594 @@
+//! Interpreter processes kept alive between runs, for the REPL behavior.
+//! The code is written to their standard input, followed by commands that print a unique
+//! marker and the exit status of the code on both stdout and stderr: everything they output
+//! before the markers is the output of the run.
+//!
+//! What a REPL remembers lives in a session, of the interpreter and, with `repl_per_buffer`,
+//! of the buffer: several languages and buffers can keep their state side by side.
+//! The sessions that are not per buffer are saved after each run for the project they were
+//! started or restored in, and restored by the first run in that project after a restart.
+
+use crate::error::SniprunError;
+use crate::job::{self, ProcessSettings};
+use crate::DataHolder;
+use log::info;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///A running interpreter, waiting for code on its standard input
+#[derive(Debug)]
+pub struct ReplProcess {
+    child: Child,
+    stdin: ChildStdin,
+    ///lines of stdout (true) and stderr (false), as they arrive
+    lines: mpsc::Receiver<(bool, String)>,
+    ///runs so far, to make the markers unique
+    runs: u32,
+    ///cleared by the threads reading the outputs when they close, so that whether the
+    ///process still runs is known without locking it
+    running: Arc<AtomicBool>,
+}
+
+impl ReplProcess {
+    ///start the interpreter, in its own process group, with the environment of the settings
+    pub fn spawn(command: &mut Command, settings: &ProcessSettings) -> Result<Self, SniprunError> {
+        if let Some(cwd) = &settings.env.cwd {
+            command.current_dir(cwd);
+        }
+        command.envs(settings.env.vars.iter().map(|(k, v)| (k, v)));
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        let mut child = command.spawn().map_err(|e| {
+            SniprunError::InternalError(format!(
+                "Unable to start process {:?}: {}",
+                command.get_program(),
+                e
+            ))
+        })?;
+        info!("[REPL] started {:?}, pid {}", command, child.id());
+
+        fn read_lines<R: Read + Send + 'static>(
+            pipe: Option<R>,
+            is_stdout: bool,
+            sender: mpsc::Sender<(bool, String)>,
+            running: Arc<AtomicBool>,
+        ) {
+            if let Some(pipe) = pipe {
+                let mut reader = BufReader::new(pipe);
+                thread::spawn(move || loop {
+                    let mut line = vec![];
+                    match reader.read_until(b'\n', &mut line) {
+                        Ok(0) | Err(_) => {
+                            running.store(false, Ordering::SeqCst);
+                            break;
+                        }
+                        Ok(_) => {
+                            let line = String::from_utf8_lossy(&line).to_string();
+                            if sender.send((is_stdout, line)).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        }
+        let (sender, lines) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        read_lines(child.stdout.take(), true, sender.clone(), running.clone());
+        read_lines(child.stderr.take(), false, sender, running.clone());
+        let stdin = child.stdin.take().unwrap();
+        Ok(ReplProcess {
+            child,
+            stdin,
+            lines,
+            runs: 0,
+            running,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    ///false once the interpreter exited, or was killed by a stop or a timeout
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    ///is_running, for whoever holds the process without its lock
+    pub fn running(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    ///a marker no output contains by chance
+    pub fn new_marker(&mut self) -> String {
+        self.runs += 1;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        format!("sniprun_end_{}_{}_{}", self.id(), self.runs, nanos)
+    }
+
+    ///Write the input (the code, then commands that print "<marker> <exit status>" on stdout
+    ///and stderr) and wait for the markers. Like run_command, the user can stop the run and it
+    ///times out, but this kills the process and whatever state it had.
+    ///If the process exits before printing the markers, the status is its own.
+    pub fn send(
+        &mut self,
+        data: &DataHolder,
+        input: &str,
+        marker: &str,
+        settings: &ProcessSettings,
+    ) -> Result<Output, SniprunError> {
+        let pgid = self.id();
+        let ReplProcess {
+            child,
+            stdin,
+            lines,
+            ..
+        } = self;
+        job::supervise(data, pgid, settings, |stream_to| {
+            stdin.write_all(input.as_bytes())?;
+            stdin.flush()?;
+
+            let mut stdout = String::new();
+            let mut stderr = String::new();
+            let mut status = None;
+            let mut ended = (false, false);
+            while !(ended.0 && ended.1) {
+                let (is_stdout, line) = match lines.recv() {
+                    Ok(line) => line,
+                    Err(_) => {
+                        info!("[REPL] process {} exited", pgid);
+                        return Ok(Output {
+                            status: child.wait()?,
+                            stdout: stdout.into_bytes(),
+                            stderr: stderr.into_bytes(),
+                        });
+                    }
+                };
+                let (text, end) = match line.find(marker) {
+                    Some(i) => (&line[..i], Some(&line[i + marker.len()..])),
+                    None => (&line[..], None),
+                };
+                if let (Some(nvim), false) = (stream_to, text.is_empty()) {
+                    job::stream_line(nvim, text);
+                }
+                if is_stdout {
+                    stdout.push_str(text);
+                    ended.0 |= end.is_some();
+                } else {
+                    stderr.push_str(text);
+                    ended.1 |= end.is_some();
+                }
+                if let Some(code) = end.and_then(|end| end.trim().parse::<i32>().ok()) {
+                    status = Some(code);
+                }
+            }
+            Ok(Output {
+                // as waitpid() reports an exit code
+                status: ExitStatus::from_raw((status.unwrap_or(1) & 0xff) << 8),
+                stdout: stdout.into_bytes(),
+                stderr: stderr.into_bytes(),
+            })
+        })
+    }
+}
+
+impl Drop for ReplProcess {
+    fn drop(&mut self) {
+        if self.is_running() {
+            info!("[REPL] stopping process {}", self.id());
+            job::kill_tree(self.id());
+            let _ = self.child.wait();
+        }
+    }
+}
+
+///Identifies a REPL session: the interpreter, and the buffer when the sessions are per buffer
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SessionKey {
+    pub interpreter: String,
+    pub buffer: Option<i64>,
+}
+
+impl SessionKey {
+    ///the session of the interpreter for the current run
+    pub fn new(data: &DataHolder, interpreter: &str) -> Self {
+        SessionKey {
+            interpreter: interpreter.to_string(),
+            buffer: if data.repl_per_buffer {
+                Some(data.buffer)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+///What a REPL remembers between runs
+#[derive(Clone, Default, Debug)]
+pub struct Session {
+    ///previous code, for the REPLs that run it again
+    pub content: String,
+    ///PID of the linked REPL, if any
+    pub pid: Option<u32>,
+    ///interpreter processes kept alive, by name, with whether they still run
+    pub processes: HashMap<String, (Arc<Mutex<ReplProcess>>, Arc<AtomicBool>)>,
+    ///the content comes from a saved session, and was not run again in a new process yet
+    pub restored: bool,
+    ///sessions_dir of the project the session is saved for: the one it was restored in, or the
+    ///one of the first run that left some content in it
+    pub project: Option<String>,
+    ///its saved session was discarded: it is not saved anymore, until it is cleared
+    pub discarded: bool,
+}
+
+///The sessions ':SnipReplMemoryClean' forgets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClearTarget {
+    All,
+    ///the sessions of that buffer, or the shared ones when sessions are not per buffer
+    Buffer(i64),
+    Interpreter(String),
+}
+
+impl ClearTarget {
+    ///from the argument of the command: nothing or "all", "buffer", or an interpreter name
+    pub fn new(argument: &str, buffer: i64) -> Self {
+        match argument.trim() {
+            "" | "all" => ClearTarget::All,
+            "buffer" => ClearTarget::Buffer(buffer),
+            name => ClearTarget::Interpreter(name.to_string()),
+        }
+    }
+
+    pub fn matches(&self, key: &SessionKey) -> bool {
+        match self {
+            ClearTarget::All => true,
+            ClearTarget::Buffer(buffer) => key.buffer.is_none() || key.buffer == Some(*buffer),
+            ClearTarget::Interpreter(name) => key.interpreter.eq_ignore_ascii_case(name),
+        }
+    }
+}
+
+///The REPL process of that name in the session of the interpreter, started with the command
+///if it is not running yet. It lives until sniprun exits or the session is cleared; without
+///interpreter data (eg: in tests), it only lives for the run
+pub fn process<F>(
+    data: &DataHolder,
+    interpreter: &str,
+    name: &str,
+    command: F,
+    settings: &ProcessSettings,
+) -> Result<Arc<Mutex<ReplProcess>>, SniprunError>
+where
+    F: FnOnce() -> Command,
+{
+    let interpreter_data = match &data.interpreter_data {
+        Some(interpreter_data) => interpreter_data,
+        None => {
+            let process = ReplProcess::spawn(&mut command(), settings)?;
+            return Ok(Arc::new(Mutex::new(process)));
+        }
+    };
+    // a run holds the lock of its process until it ends, and may need the interpreter data
+    // meanwhile: the process is not locked here
+    let mut interpreter_data = interpreter_data.lock().unwrap();
+    let session = interpreter_data.session(&SessionKey::new(data, interpreter));
+    if let Some((process, running)) = session.processes.get(name) {
+        if running.load(Ordering::SeqCst) {
+            return Ok(process.clone());
+        }
+        info!(
+            "[REPL] the process {} of {} is not running anymore",
+            name, interpreter
+        );
+    }
+    let process = ReplProcess::spawn(&mut command(), settings)?;
+    let running = process.running();
+    let process = Arc::new(Mutex::new(process));
+    if !session.restored {
+        // what the previous process ran is lost with it
+        session.content.clear();
+    }
+    session
+        .processes
+        .insert(name.to_string(), (process.clone(), running));
+    Ok(process)
+}
+
+///The code a restored session of the interpreter ran before the restart, once: REPLs that
+///keep their state in a process run it again in the new one, and save the code they run
+pub fn restored_code(data: &DataHolder, interpreter: &str) -> Option<String> {
+    let mut interpreter_data = data.interpreter_data.as_ref()?.lock().unwrap();
+    let session = interpreter_data.session(&SessionKey::new(data, interpreter));
+    if !session.restored {
+        return None;
+    }
+    session.restored = false;
+    Some(session.content.clone()).filter(|code| !code.trim().is_empty())
+}
+
+///Where the sessions of the project of the current file (or of its directory, if it is in no
+///project) are saved, along with the files interpreters dump their state to
+pub fn sessions_dir(data: &DataHolder) -> String {
+    let project = if !data.projectroot.is_empty() {
+        data.projectroot.clone()
+    } else {
+        Path::new(&data.filepath)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    if project.is_empty() {
+        return format!("{}/sessions/default", data.work_dir);
+    }
+    // like vim's undo files
+    format!("{}/sessions/{}", data.work_dir, project.replace('/', "%"))
+}
+
+///Save the content of the sessions that are not per buffer and belong to the project of the
+///current file: the sessions of other projects are not mixed in its file
+pub fn save_sessions(data: &DataHolder) {
+    let mut interpreter_data = match &data.interpreter_data {
+        Some(interpreter_data) => interpreter_data.lock().unwrap(),
+        None => return,
+    };
+    let dir = sessions_dir(data);
+    let mut sessions = serde_json::Map::new();
+    for (key, session) in interpreter_data.sessions.iter_mut() {
+        if key.buffer.is_some() || session.content.is_empty() || session.discarded {
+            continue;
+        }
+        if session.project.get_or_insert_with(|| dir.clone()) == &dir {
+            sessions.insert(key.interpreter.clone(), session.content.clone().into());
+        }
+    }
+    let saved = std::fs::create_dir_all(&dir).and_then(|_| {
+        std::fs::write(
+            dir + "/sessions.json",
+            serde_json::Value::Object(sessions).to_string(),
+        )
+    });
+    if let Err(e) = saved {
+        info!("[REPL] could not save the sessions: {}", e);
+    }
+}
+
+///Restore the saved sessions of the project of the current file, the first time a snippet
+///runs in that project. The sessions already in use are kept as they are
+pub fn restore_sessions(data: &DataHolder) {
+    let mut interpreter_data = match &data.interpreter_data {
+        Some(interpreter_data) => interpreter_data.lock().unwrap(),
+        None => return,
+    };
+    let dir = sessions_dir(data);
+    if !interpreter_data.restored_projects.insert(dir.clone()) {
+        return;
+    }
+    let saved = match std::fs::read_to_string(dir.clone() + "/sessions.json") {
+        Ok(saved) => saved,
+        Err(_) => return,
+    };
+    let sessions: HashMap<String, String> = match serde_json::from_str(&saved) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            info!("[REPL] invalid saved sessions: {}", e);
+            return;
+        }
+    };
+    for (interpreter, content) in sessions {
+        let key = SessionKey {
+            interpreter,
+            buffer: None,
+        };
+        if !interpreter_data.sessions.contains_key(&key) {
+            info!("[REPL] restored the session of {}", key.interpreter);
+            let session = interpreter_data.session(&key);
+            session.content = content;
+            session.restored = true;
+            session.project = Some(dir.clone());
+        }
+    }
+}
+
+///Delete the saved sessions of the project of the current file. The running ones are kept, but
+///not saved anymore: the next runs would write them again otherwise
+pub fn discard_sessions(data: &DataHolder) -> std::io::Result<()> {
+    let dir = sessions_dir(data);
+    if let Some(interpreter_data) = &data.interpreter_data {
+        let mut interpreter_data = interpreter_data.lock().unwrap();
+        for session in interpreter_data.sessions.values_mut() {
+            if session.project.as_ref() == Some(&dir) {
+                session.discarded = true;
+                if session.restored {
+                    // it did not run again yet, and won't
+                    session.restored = false;
+                    session.content.clear();
+                }
+            }
+        }
+    }
+    if Path::new(&dir).exists() {
+        std::fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_repl {
+    use super::*;
+    use crate::InterpreterData;
+
+    fn run(process: &Arc<Mutex<ReplProcess>>, code: &str) -> Output {
+        let mut process = process.lock().unwrap();
+        let marker = process.new_marker();
+        let input = format!(
+            "{}\nstatus=$?\necho \"{m} $status\"\necho \"{m} $status\" >&2\n",
+            code,
+            m = marker
+        );
+        process
+            .send(
+                &DataHolder::new(),
+                &input,
+                &marker,
+                &ProcessSettings::default(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn keep_state() {
+        let mut data = DataHolder::new();
+        data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        let sh = || Command::new("sh");
+        let settings = ProcessSettings::default();
+
+        let process = super::process(&data, "Sh", "sh", sh, &settings).unwrap();
+        let output = run(&process, "A=1; printf 'no newline'");
+        assert_eq!(output.stdout, b"no newline".to_vec());
+        assert!(output.status.success());
+
+        // found without locking the process, that a run may hold
+        let guard = process.lock().unwrap();
+        let same = super::process(&data, "Sh", "sh", sh, &settings).unwrap();
+        drop(guard);
+        assert!(Arc::ptr_eq(&process, &same));
+
+        let process = super::process(&data, "Sh", "sh", sh, &settings).unwrap();
+        let output = run(&process, "echo $A; echo oops >&2; false");
+        assert_eq!(output.stdout, b"1\n".to_vec());
+        assert_eq!(output.stderr, b"oops\n".to_vec());
+        assert_eq!(output.status.code(), Some(1));
+
+        // the state is lost with the process
+        let output = run(&process, "exit 3");
+        assert_eq!(output.status.code(), Some(3));
+        let process = super::process(&data, "Sh", "sh", sh, &settings).unwrap();
+        assert_eq!(run(&process, "echo \"[$A]\"").stdout, b"[]\n".to_vec());
+    }
+
+    #[test]
+    fn sessions() {
+        let interpreter_data = Arc::new(Mutex::new(InterpreterData::default()));
+        let mut data = DataHolder::new();
+        data.interpreter_data = Some(interpreter_data.clone());
+        data.repl_per_buffer = true;
+        let sh = || Command::new("sh");
+        let settings = ProcessSettings::default();
+
+        // a session per interpreter and per buffer
+        data.buffer = 1;
+        let process = super::process(&data, "Sh", "sh", sh, &settings).unwrap();
+        run(&process, "A=1");
+        let process = super::process(&data, "Other", "sh", sh, &settings).unwrap();
+        assert_eq!(run(&process, "echo \"[$A]\"").stdout, b"[]\n".to_vec());
+        data.buffer = 2;
+        let process = super::process(&data, "Sh", "sh", sh, &settings).unwrap();
+        assert_eq!(run(&process, "echo \"[$A]\"").stdout, b"[]\n".to_vec());
+
+        let clear = |target: &str, buffer: i64| {
+            interpreter_data
+                .lock()
+                .unwrap()
+                .clear(&ClearTarget::new(target, buffer))
+        };
+        assert_eq!(clear("buffer", 2), 1);
+        assert_eq!(clear("sh", 1), 1);
+        data.buffer = 1;
+        let process = super::process(&data, "Other", "sh", sh, &settings).unwrap();
+        assert_eq!(run(&process, "echo ok").stdout, b"ok\n".to_vec());
+        assert_eq!(clear("all", 1), 1);
+        assert_eq!(clear("all", 1), 0);
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let mut data = DataHolder::new();
+        data.work_dir = std::env::temp_dir()
+            .join(format!("sniprun_test_sessions_{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        data.filepath = String::from("/home/me/project/main.sh");
+        data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        {
+            let mut interpreter_data = data.interpreter_data.as_ref().unwrap().lock().unwrap();
+            let key = SessionKey::new(&data, "Sh");
+            interpreter_data.session(&key).content = String::from("A=1");
+        }
+        save_sessions(&data);
+        assert!(sessions_dir(&data).ends_with("/sessions/%home%me%project"));
+
+        // after a restart
+        data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        restore_sessions(&data);
+        assert_eq!(restored_code(&data, "Sh"), Some(String::from("A=1")));
+        assert_eq!(restored_code(&data, "Sh"), None);
+        assert_eq!(restored_code(&data, "Other"), None);
+
+        // the sessions of the next runs are not saved again
+        discard_sessions(&data).unwrap();
+        {
+            let mut interpreter_data = data.interpreter_data.as_ref().unwrap().lock().unwrap();
+            let key = SessionKey::new(&data, "Sh");
+            interpreter_data.session(&key).content += "\nB=2";
+        }
+        save_sessions(&data);
+        data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        restore_sessions(&data);
+        assert_eq!(restored_code(&data, "Sh"), None);
+        std::fs::remove_dir_all(&data.work_dir).unwrap();
+    }
+
+    #[test]
+    fn sessions_of_each_project() {
+        let mut data = DataHolder::new();
+        data.work_dir = std::env::temp_dir()
+            .join(format!("sniprun_test_projects_{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+        let run = |data: &mut DataHolder, file: &str, interpreter: &str| {
+            data.filepath = String::from(file);
+            restore_sessions(data);
+            {
+                let mut interpreter_data = data.interpreter_data.as_ref().unwrap().lock().unwrap();
+                let key = SessionKey::new(data, interpreter);
+                interpreter_data.session(&key).content += "A=1";
+            }
+            save_sessions(data);
+        };
+        run(&mut data, "/home/me/one/main.sh", "Sh");
+        run(&mut data, "/home/me/two/main.py", "Py");
+        // the session of 'one' keeps being saved there only
+        run(&mut data, "/home/me/two/main.sh", "Sh");
+        run(&mut data, "/home/me/one/main.sh", "Sh");
+
+        // after a restart
+        let restart = |data: &mut DataHolder, file: &str| {
+            data.filepath = String::from(file);
+            data.interpreter_data = Some(Arc::new(Mutex::new(InterpreterData::default())));
+            restore_sessions(data);
+        };
+        restart(&mut data, "/home/me/two/main.py");
+        assert_eq!(restored_code(&data, "Sh"), None);
+        assert_eq!(restored_code(&data, "Py"), Some(String::from("A=1")));
+        restart(&mut data, "/home/me/one/main.sh");
+        assert_eq!(restored_code(&data, "Sh"), Some(String::from("A=1A=1A=1")));
+        assert_eq!(restored_code(&data, "Py"), None);
+        std::fs::remove_dir_all(&data.work_dir).unwrap();
+    }
+}
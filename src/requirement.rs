@@ -0,0 +1,134 @@
+//! External executables the interpreters need, and how to find them
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+///how long a version probe may run before it is killed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+///An executable an interpreter runs, see Interpreter::get_requirements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub executable: String,
+    ///what it is used for, if the interpreter works without it
+    pub optional: Option<String>,
+    ///arguments that make the executable print its version
+    pub version_args: Vec<String>,
+}
+
+impl Requirement {
+    pub fn required(executable: &str, version_args: &[&str]) -> Self {
+        Requirement {
+            executable: executable.to_string(),
+            optional: None,
+            version_args: version_args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    pub fn optional(executable: &str, version_args: &[&str], purpose: &str) -> Self {
+        Requirement {
+            optional: Some(purpose.to_string()),
+            ..Requirement::required(executable, version_args)
+        }
+    }
+
+    ///where the executable is, from $PATH (or as is if it is a path)
+    pub fn find(&self) -> Option<PathBuf> {
+        if self.executable.contains('/') {
+            return Some(PathBuf::from(&self.executable)).filter(|p| is_executable(p));
+        }
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|dir| dir.join(&self.executable))
+            .find(|path| is_executable(path))
+    }
+
+    ///first line printed by the version probe (on stdout or stderr)
+    pub fn version(&self) -> Option<String> {
+        let mut child = Command::new(self.find()?)
+            .args(&self.version_args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let start = Instant::now();
+        while child.try_wait().ok()?.is_none() {
+            if start.elapsed() > PROBE_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let output = child.wait_with_output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        stdout
+            .lines()
+            .chain(stderr.lines())
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(String::from)
+    }
+
+    ///"found <path> (<version>)", or what is missing and why it matters
+    pub fn status(&self) -> String {
+        match (self.find(), &self.optional) {
+            (Some(path), _) => format!(
+                "found {} ({})",
+                path.display(),
+                self.version()
+                    .unwrap_or_else(|| String::from("unknown version"))
+            ),
+            (None, None) => format!("missing {} (required)", self.executable),
+            (None, Some(purpose)) => format!("missing {} (optional: {})", self.executable, purpose),
+        }
+    }
+}
+
+///the required executables that can't be found
+pub fn missing(requirements: &[Requirement]) -> Vec<String> {
+    requirements
+        .iter()
+        .filter(|r| r.optional.is_none() && r.find().is_none())
+        .map(|r| r.executable.clone())
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod test_requirement {
+    use super::*;
+
+    #[test]
+    fn find_and_probe() {
+        let sh = Requirement::required("sh", &["-c", "echo 'sh 1.0'"]);
+        assert!(sh.find().is_some());
+        assert_eq!(sh.version(), Some(String::from("sh 1.0")));
+        assert!(sh.status().starts_with("found "));
+
+        let missing_tool = Requirement::required("sniprun-no-such-tool", &["--version"]);
+        let optional_tool = Requirement::optional("sniprun-no-such-tool", &[], "the REPL");
+        assert_eq!(
+            missing_tool.status(),
+            "missing sniprun-no-such-tool (required)"
+        );
+        assert_eq!(
+            missing(&[sh, missing_tool, optional_tool]),
+            vec![String::from("sniprun-no-such-tool")]
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::environment::expand_path;
+use crate::error::SniprunError;
+use crate::DataHolder;
+use neovim_lib::{NeovimApi, Value};
+use std::str::FromStr;
+
+///Where the standard input of the snippet comes from, written as `<kind>:<what>`:
+/// - `file:<path>` a file, relative paths start from the directory of the current file
+/// - `register:<name>` the content of a neovim register
+/// - `buffer:<name or number>` the lines of a neovim buffer
+/// - `text:<literal>` the given text, `\n` are replaced by newlines
+///
+///Without any, the snippet reads from /dev/null
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StdinSource {
+    File(String),
+    Register(String),
+    Buffer(String),
+    Text(String),
+}
+
+impl FromStr for StdinSource {
+    type Err = SniprunError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("file", path)) => Ok(StdinSource::File(path.trim().to_string())),
+            Some(("register", reg)) => Ok(StdinSource::Register(reg.trim().to_string())),
+            Some(("buffer", buf)) => Ok(StdinSource::Buffer(buf.trim().to_string())),
+            Some(("text", text)) => Ok(StdinSource::Text(text.to_string())),
+            _ => Err(SniprunError::CustomError(format!(
+                "Invalid stdin: '{}', expected file:<path>, register:<name>, buffer:<name> or text:<text>",
+                s
+            ))),
+        }
+    }
+}
+
+impl StdinSource {
+    ///read the content to feed to the snippet
+    pub fn read(&self, data: &DataHolder) -> Result<Vec<u8>, SniprunError> {
+        let content = match self {
+            StdinSource::File(path) => {
+                let path = expand_path(path, data);
+                return std::fs::read(&path).map_err(|e| {
+                    SniprunError::CustomError(format!("Could not read stdin from {}: {}", path, e))
+                });
+            }
+            StdinSource::Text(text) => text.replace("\\n", "\n"),
+            StdinSource::Register(reg) => {
+                let res = call_nvim(data, "getreg", vec![Value::from(reg.as_str())])?;
+                res.as_str().unwrap_or_default().to_string()
+            }
+            StdinSource::Buffer(buf) => {
+                let buf = match buf.parse::<i64>() {
+                    Ok(number) => Value::from(number),
+                    Err(_) => Value::from(buf.as_str()),
+                };
+                let exists = call_nvim(data, "bufexists", vec![buf.clone()])?;
+                if exists.as_i64() != Some(1) {
+                    return Err(SniprunError::CustomError(format!(
+                        "Could not read stdin: no buffer {}",
+                        buf
+                    )));
+                }
+                let lines = call_nvim(
+                    data,
+                    "getbufline",
+                    vec![buf, Value::from(1), Value::from("$")],
+                )?;
+                lines
+                    .as_array()
+                    .unwrap_or(&vec![])
+                    .iter()
+                    .map(|l| l.as_str().unwrap_or_default().to_string() + "\n")
+                    .collect()
+            }
+        };
+        // programs reading lines expect the last one to be terminated
+        if content.is_empty() || content.ends_with('\n') {
+            Ok(content.into_bytes())
+        } else {
+            Ok((content + "\n").into_bytes())
+        }
+    }
+}
+
+fn call_nvim(data: &DataHolder, function: &str, args: Vec<Value>) -> Result<Value, SniprunError> {
+    let nvim = data.nvim_instance.as_ref().ok_or_else(|| {
+        SniprunError::InternalError(String::from(
+            "Could not read stdin: neovim is not available",
+        ))
+    })?;
+    let res = nvim.lock().unwrap().call_function(function, args);
+    res.map_err(|e| SniprunError::CustomError(format!("Could not read stdin: {}", e)))
+}
+
+#[cfg(test)]
+mod test_stdin {
+    use super::*;
+
+    #[test]
+    fn parse_and_read() {
+        let mut data = DataHolder::new();
+        let source = StdinSource::from_str("text:1\\n2").unwrap();
+        assert_eq!(source, StdinSource::Text(String::from("1\\n2")));
+        assert_eq!(source.read(&data).unwrap(), b"1\n2\n".to_vec());
+        assert!(StdinSource::from_str("in.txt").is_err());
+
+        data.filepath = String::from("/tmp/sniprun_stdin_test/main.py");
+        std::fs::create_dir_all("/tmp/sniprun_stdin_test").unwrap();
+        std::fs::write("/tmp/sniprun_stdin_test/in.txt", "from file").unwrap();
+        let source = StdinSource::from_str("file:in.txt").unwrap();
+        assert_eq!(source.read(&data).unwrap(), b"from file".to_vec());
+    }
+}
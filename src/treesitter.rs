@@ -0,0 +1,210 @@
+//! Syntax nodes of the current buffer, asked to neovim's treesitter parser
+
+use crate::error::SniprunError;
+use crate::DataHolder;
+use log::info;
+use neovim_lib::{NeovimApi, Value};
+
+///Which code to run around the lines sent by the lua side
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeMode {
+    ///the lines themselves, or the statement a lone fragment is part of
+    Selection,
+    ///the function, class or block around the cursor
+    Enclosing,
+}
+
+impl From<&str> for RangeMode {
+    fn from(mode: &str) -> Self {
+        match mode {
+            "enclosing" => RangeMode::Enclosing,
+            _ => RangeMode::Selection,
+        }
+    }
+}
+
+///returns the lines (1-based, inclusive) of the node and whether the lines parse on their own,
+///or nil without a parser or a suitable node
+const NODE_RANGE: &str = r#"
+local first, last, enclosing = ...
+local ok, parser = pcall(vim.treesitter.get_parser, 0)
+if not ok or not parser then return nil end
+local root = parser:parse()[1]:root()
+
+local line = vim.api.nvim_buf_get_lines(0, first - 1, first, false)[1] or ""
+local last_line = vim.api.nvim_buf_get_lines(0, last - 1, last, false)[1] or ""
+local col = (line:find("%S") or 1) - 1
+local end_col = math.max(#(last_line:gsub("%s+$", "")) - 1, col)
+local node = root:named_descendant_for_range(first - 1, col, last - 1, end_col)
+
+local function lines(n)
+  local start_row, _, end_row, end_col = n:range()
+  if end_col == 0 and end_row > start_row then return { start_row + 1, end_row } end
+  return { start_row + 1, end_row + 1 }
+end
+
+if enclosing then
+  local blocks = nil
+  while node and node ~= root do
+    local kind = node:type()
+    for _, pattern in ipairs({ "function", "method", "class", "closure", "lambda", "impl", "def", "procedure" }) do
+      if kind:find(pattern) then return lines(node) end
+    end
+    if not blocks and (kind:find("block") or kind:find("statement")) then blocks = node end
+    node = node:parent()
+  end
+  return blocks and lines(blocks)
+end
+
+-- whether the line is a complete piece of code, or a fragment of a bigger statement
+local alone = line:gsub("^%s+", "")
+local ok_alone, line_parser = pcall(vim.treesitter.get_string_parser, alone, parser:lang())
+local complete = not ok_alone or not line_parser:parse()[1]:root():has_error()
+
+-- the smallest node that covers the whole line
+while node and node ~= root do
+  local start_row, start_col, end_row, node_end_col = node:range()
+  local covers_start = start_row < first - 1 or (start_row == first - 1 and start_col <= col)
+  local covers_end = end_row > last - 1 or (end_row == last - 1 and node_end_col > end_col)
+  if covers_start and covers_end then
+    if node:has_error() then return nil end
+    local range = lines(node)
+    return { range[1], range[2], complete }
+  end
+  node = node:parent()
+end
+return nil
+"#;
+
+///the lines of the node to run and whether the lines sent are complete,
+///if the buffer has a treesitter parser
+fn node_range(data: &DataHolder, enclosing: bool) -> Option<([i64; 2], bool)> {
+    let nvim = data.nvim_instance.as_ref()?;
+    let result = nvim.lock().unwrap().execute_lua(
+        NODE_RANGE,
+        vec![
+            Value::from(data.range[0]),
+            Value::from(data.range[1]),
+            Value::from(enclosing),
+        ],
+    );
+    match result {
+        Ok(value) => range_from_value(&value),
+        Err(e) => {
+            info!("[TREESITTER] could not get the syntax node: {}", e);
+            None
+        }
+    }
+}
+
+fn range_from_value(value: &Value) -> Option<([i64; 2], bool)> {
+    match value.as_array()?.as_slice() {
+        [start, end] => Some(([start.as_i64()?, end.as_i64()?], true)),
+        [start, end, complete] => Some(([start.as_i64()?, end.as_i64()?], complete.as_bool()?)),
+        _ => None,
+    }
+}
+
+///the lines to run instead of the selected ones, if any: the enclosing node, or the statement
+///a single line is in the middle or at the end of, when that line doesn't parse on its own
+fn expanded_range(
+    mode: RangeMode,
+    range: [i64; 2],
+    node: Option<([i64; 2], bool)>,
+) -> Result<Option<[i64; 2]>, SniprunError> {
+    let node = match (mode, node) {
+        (RangeMode::Enclosing, None) => return Err(SniprunError::CustomError(String::from(
+            "No enclosing function or block found, is there a treesitter parser for this filetype?",
+        ))),
+        (RangeMode::Enclosing, Some((node, _))) => node,
+        // a complete line runs as it is, and so does the first line of a statement
+        // (eg: 'def f():' or a loop header), like without treesitter
+        (RangeMode::Selection, Some((node, false)))
+            if range[0] == range[1] && node[0] < range[0] =>
+        {
+            node
+        }
+        (RangeMode::Selection, _) => return Ok(None),
+    };
+    Ok(Some(node).filter(|node| *node != range))
+}
+
+///Replace the range and current_bloc by the ones of the syntax node to run: the enclosing
+///function or block, or for a single line that is a fragment, the statement it is part of
+pub fn fetch_node(data: &mut DataHolder) -> Result<(), SniprunError> {
+    let enclosing = data.range_mode == RangeMode::Enclosing;
+    if !enclosing && data.range[0] != data.range[1] {
+        return Ok(());
+    }
+    let node = node_range(data, enclosing);
+    let range = match expanded_range(data.range_mode, data.range, node)? {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+    info!(
+        "[TREESITTER] running lines {:?} instead of {:?}",
+        range, data.range
+    );
+
+    let nvim = data.nvim_instance.clone().unwrap();
+    let mut nvim = nvim.lock().unwrap();
+    let lines = nvim
+        .get_current_buf()
+        .and_then(|buffer| buffer.get_lines(&mut nvim, range[0] - 1, range[1], false))
+        .map_err(|e| SniprunError::CustomError(format!("Could not get the code to run: {}", e)))?;
+    data.current_bloc = lines.join("\n");
+    data.range = range;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_treesitter {
+    use super::*;
+
+    #[test]
+    fn parse_range() {
+        let value = Value::Array(vec![Value::from(3), Value::from(7)]);
+        assert_eq!(range_from_value(&value), Some(([3, 7], true)));
+        let value = Value::Array(vec![Value::from(3), Value::from(7), Value::from(false)]);
+        assert_eq!(range_from_value(&value), Some(([3, 7], false)));
+        assert_eq!(range_from_value(&Value::Nil), None);
+        assert_eq!(RangeMode::from("enclosing"), RangeMode::Enclosing);
+        assert_eq!(RangeMode::from(""), RangeMode::Selection);
+    }
+
+    #[test]
+    fn expansion() {
+        let selection = RangeMode::Selection;
+        // a complete line, or the first line of a statement ('def f():', a loop header...)
+        assert_eq!(
+            expanded_range(selection, [3, 3], Some(([3, 7], true))),
+            Ok(None)
+        );
+        assert_eq!(
+            expanded_range(selection, [3, 3], Some(([3, 7], false))),
+            Ok(None)
+        );
+        // a fragment inside a statement spanning several lines runs the whole statement
+        assert_eq!(
+            expanded_range(selection, [4, 4], Some(([3, 7], false))),
+            Ok(Some([3, 7]))
+        );
+        // several lines, or no parser: the selection runs as it is
+        assert_eq!(
+            expanded_range(selection, [4, 5], Some(([3, 7], false))),
+            Ok(None)
+        );
+        assert_eq!(expanded_range(selection, [4, 4], None), Ok(None));
+
+        let enclosing = RangeMode::Enclosing;
+        assert_eq!(
+            expanded_range(enclosing, [4, 4], Some(([1, 9], true))),
+            Ok(Some([1, 9]))
+        );
+        assert_eq!(
+            expanded_range(enclosing, [1, 9], Some(([1, 9], true))),
+            Ok(None)
+        );
+        assert!(expanded_range(enclosing, [4, 4], None).is_err());
+    }
+}